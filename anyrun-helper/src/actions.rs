@@ -0,0 +1,62 @@
+use abi_stable::std_types::{ROption, RVec};
+use anyrun_plugin::{HandleResult, MatchAction};
+use std::{os::unix::ffi::OsStrExt, path::Path, process::Command};
+
+use crate::terminal::get_available_terminal;
+
+pub const OPEN_FOLDER: &str = "open-folder";
+pub const COPY_PATH: &str = "copy-path";
+pub const OPEN_TERMINAL: &str = "open-terminal";
+
+/// The secondary actions shared by all plugins whose matches point to a path
+pub fn path_actions() -> RVec<MatchAction> {
+    vec![
+        MatchAction {
+            id: OPEN_FOLDER.into(),
+            title: "Open containing folder".into(),
+            icon: ROption::RSome("folder-open".into()),
+            keybind: ROption::RSome("Ctrl+o".into()),
+        },
+        MatchAction {
+            id: COPY_PATH.into(),
+            title: "Copy path".into(),
+            icon: ROption::RSome("edit-copy".into()),
+            keybind: ROption::RSome("Ctrl+Shift+c".into()),
+        },
+        MatchAction {
+            id: OPEN_TERMINAL.into(),
+            title: "Open in terminal".into(),
+            icon: ROption::RSome("utilities-terminal".into()),
+            keybind: ROption::RSome("Ctrl+t".into()),
+        },
+    ]
+    .into()
+}
+
+/// Runs one of the `path_actions` on `path`
+pub fn handle_path_action(action: &str, path: &Path) -> HandleResult {
+    let folder = path.parent().unwrap_or(path);
+
+    match action {
+        OPEN_FOLDER => {
+            if let Err(why) = Command::new("xdg-open").arg(folder).spawn() {
+                eprintln!("[anyrun-helper] Error running xdg-open: {why}");
+            }
+        }
+        COPY_PATH => return HandleResult::Copy(path.as_os_str().as_bytes().into()),
+        OPEN_TERMINAL => {
+            let dir = if path.is_dir() { path } else { folder };
+            match get_available_terminal() {
+                Some(terminal) => {
+                    if let Err(why) = Command::new(&terminal).current_dir(dir).spawn() {
+                        eprintln!("[anyrun-helper] Error running {terminal}: {why}");
+                    }
+                }
+                None => eprintln!("[anyrun-helper] No terminal available"),
+            }
+        }
+        _ => eprintln!("[anyrun-helper] Unknown path action: {action}"),
+    }
+
+    HandleResult::Close
+}
@@ -1,5 +1,6 @@
 use arboard::Clipboard;
 
+pub mod actions;
 pub mod icon;
 pub mod log;
 pub mod mazzy_matcher;
@@ -58,6 +59,13 @@ pub fn set_clipboard(content: String) -> Result<(), arboard::Error> {
     clipboard.set_text(content)
 }
 
+/// Escapes text so it can be embedded in Pango markup
+pub fn escape_markup(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
 pub fn logger(msg: &str) {
     use std::io::Write;
     let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".into());
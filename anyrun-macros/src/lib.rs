@@ -125,6 +125,247 @@ pub fn get_matches(_attr: TokenStream, item: TokenStream) -> TokenStream {
     .into()
 }
 
+/// Optional function that finds the matches in batches, instead of `get_matches` returning them all at once. Takes
+/// the current text input as an `RString` as the first argument, a `&MatchSink` as the second argument, and the
+/// third argument can be one of:
+/// - &T
+/// - &mut T
+/// - <Nothing>
+/// where T is the type returned by `init`.
+///
+/// Each batch passed to `MatchSink::push` is shown right away, below the ones pushed before. Stop pushing once it
+/// returns `false`, the query is outdated then. The sink can't be used after the function returns. `get_matches`
+/// is still required, and should return all the matches at once for providers that don't support streaming.
+#[proc_macro_attribute]
+pub fn stream_matches(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let function = parse_macro_input!(item as syn::ItemFn);
+    let fn_name = &function.sig.ident;
+
+    let fn_call = match shared_data(&function, 3) {
+        Ok(Some(data)) => quote! {
+            if let ::core::option::Option::Some(data) = #data {
+                #fn_name(input, sink, data)
+            }
+        },
+        Ok(None) => quote! {
+            #fn_name(input, sink)
+        },
+        Err(error) => return error,
+    };
+
+    quote! {
+        #[::abi_stable::sabi_extern_fn]
+        #[unsafe(no_mangle)]
+        fn anyrun_internal_stream_matches(
+            input: ::abi_stable::std_types::RString,
+            sink: &::anyrun_plugin::MatchSink,
+        ) {
+            #function
+
+            if ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| {
+                #fn_call
+            }))
+            .is_err()
+            {
+                ::std::eprintln!("Plugin '{}' panicked", anyrun_internal_info().name);
+            }
+        }
+    }
+    .into()
+}
+
+/// Optional function that provides the secondary actions of a match. Takes a `&Match` as its first argument,
+/// and the second argument can be one of:
+/// - &T
+/// - &mut T
+/// - <Nothing>
+/// where T is the type returned by `init`.
+///
+/// Should return an `RVec` of `MatchAction`s, which are handled by the `action_handler`.
+#[proc_macro_attribute]
+pub fn actions(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let function = parse_macro_input!(item as syn::ItemFn);
+    let fn_name = &function.sig.ident;
+
+    let fn_call = match shared_data(&function, 3) {
+        Ok(Some(data)) => quote! {
+            if let ::core::option::Option::Some(data) = #data {
+                #fn_name(input, &selection, data)
+            } else {
+                ::abi_stable::std_types::RVec::new()
+            }
+        },
+        Ok(None) => quote! {
+            #fn_name(input, &selection)
+        },
+        Err(error) => return error,
+    };
+
+    quote! {
+        #[::abi_stable::sabi_extern_fn]
+        #[unsafe(no_mangle)]
+        fn anyrun_internal_actions(
+            selection: ::anyrun_plugin::anyrun_interface::Match,
+        ) -> ::abi_stable::std_types::RVec<::anyrun_plugin::MatchAction> {
+            #function
+
+            #fn_call
+        }
+    }
+    .into()
+}
+
+/// Optional function to handle a secondary action of a match. Takes a `Match` as its first argument, the `id` of the
+/// chosen `MatchAction` as an `RString` as the second argument, and the third argument can be one of:
+/// - &T
+/// - &mut T
+/// - <Nothing>
+/// where T is the type returned by `init`.
+///
+/// Should return a `HandleResult` with the appropriate action.
+#[proc_macro_attribute]
+pub fn action_handler(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let function = parse_macro_input!(item as syn::ItemFn);
+    let fn_name = &function.sig.ident;
+
+    let data = match shared_data(&function, 3) {
+        Ok(Some(data)) => quote! {
+            #data.unwrap(),
+        },
+        Ok(None) => quote! {},
+        Err(error) => return error,
+    };
+
+    quote! {
+        #[::abi_stable::sabi_extern_fn]
+        #[unsafe(no_mangle)]
+        fn anyrun_internal_handle_action(
+            selection: ::anyrun_plugin::anyrun_interface::Match,
+            action: ::abi_stable::std_types::RString,
+        ) -> ::anyrun_plugin::anyrun_interface::HandleResult {
+            #function
+
+            #fn_name(
+                selection,
+                action,
+                #data
+            )
+        }
+    }
+    .into()
+}
+
+/// Optional function that provides a preview of a match, shown in the preview pane. Takes a `&Match` as its first
+/// argument, and the second argument can be one of:
+/// - &T
+/// - &mut T
+/// - <Nothing>
+/// where T is the type returned by `init`.
+///
+/// Should return an `ROption<Preview>`, `RNone` if the match has nothing to preview. It is only called for the
+/// selected match while the preview pane is shown, so it may do more expensive work than `get_matches`.
+#[proc_macro_attribute]
+pub fn preview(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let function = parse_macro_input!(item as syn::ItemFn);
+    let fn_name = &function.sig.ident;
+
+    let fn_call = match shared_data(&function, 3) {
+        Ok(Some(data)) => quote! {
+            if let ::core::option::Option::Some(data) = #data {
+                #fn_name(input, &selection, data)
+            } else {
+                ::abi_stable::std_types::ROption::RNone
+            }
+        },
+        Ok(None) => quote! {
+            #fn_name(input, &selection)
+        },
+        Err(error) => return error,
+    };
+
+    quote! {
+        #[::abi_stable::sabi_extern_fn]
+        #[unsafe(no_mangle)]
+        fn anyrun_internal_preview(
+            selection: ::anyrun_plugin::anyrun_interface::Match,
+        ) -> ::abi_stable::std_types::ROption<::anyrun_plugin::Preview> {
+            #function
+
+            #fn_call
+        }
+    }
+    .into()
+}
+
+/// Optional function that reports how relevant a match is to the query it was returned for, used to rank the
+/// matches of all plugins together. Takes the query as an `RString` and the `&Match` returned for it as its first two
+/// arguments, and the third argument can be one of:
+/// - &T
+/// - &mut T
+/// - <Nothing>
+/// where T is the type returned by `init`.
+///
+/// Should return an `ROption<f64>` in the range `0.0..=1.0`, or `RNone` to leave the score of the match to the
+/// provider, which guesses it from the order of the matches and how well they fit the query.
+#[proc_macro_attribute]
+pub fn score(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let function = parse_macro_input!(item as syn::ItemFn);
+    let fn_name = &function.sig.ident;
+
+    let fn_call = match shared_data(&function, 3) {
+        Ok(Some(data)) => quote! {
+            if let ::core::option::Option::Some(data) = #data {
+                #fn_name(input, &selection, data)
+            } else {
+                ::abi_stable::std_types::ROption::RNone
+            }
+        },
+        Ok(None) => quote! {
+            #fn_name(input, &selection)
+        },
+        Err(error) => return error,
+    };
+
+    quote! {
+        #[::abi_stable::sabi_extern_fn]
+        #[unsafe(no_mangle)]
+        fn anyrun_internal_score(
+            input: ::abi_stable::std_types::RString,
+            selection: ::anyrun_plugin::anyrun_interface::Match,
+        ) -> ::abi_stable::std_types::ROption<f64> {
+            #function
+
+            #fn_call
+        }
+    }
+    .into()
+}
+
+/// Returns the expression borrowing the shared data as an `Option`, if the function takes it
+/// as the argument following the `args` it always has.
+fn shared_data(
+    function: &syn::ItemFn,
+    args: usize,
+) -> Result<Option<proc_macro2::TokenStream>, TokenStream> {
+    if function.sig.inputs.len() != args {
+        return Ok(None);
+    }
+
+    match function.sig.inputs.last() {
+        ::core::option::Option::Some(syn::FnArg::Typed(pat)) => match &*pat.ty {
+            Type::Reference(reference) if reference.mutability.is_some() => Ok(Some(quote! {
+                ANYRUN_INTERNAL_DATA.write().unwrap().as_mut()
+            })),
+            Type::Reference(_) => Ok(Some(quote! {
+                ANYRUN_INTERNAL_DATA.read().unwrap().as_ref()
+            })),
+            _ => Err(quote! { compile_error!("Last argument must be either a reference to the shared data or should not be present at all.") }.into()),
+        },
+        ::core::option::Option::Some(_) => Err(quote! { compile_error!("`self` argument, really?") }.into()),
+        ::core::option::Option::None => unreachable!(),
+    }
+}
+
 /// Function that returns the plugin info as a `PluginInfo` object. Takes no arguments.
 #[proc_macro_attribute]
 pub fn info(_attr: TokenStream, item: TokenStream) -> TokenStream {
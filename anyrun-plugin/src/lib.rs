@@ -3,10 +3,97 @@ The crate for building plugins for Anyrun.
 
 Each plugin needs 4 functions defined, `init`, `info`, `get_matches` and the `handler`. Documentation
 on what each of these should be is found in their respective attribute macros.
+
+Plugins can optionally provide secondary actions for their matches by defining both `actions` and
+the `action_handler`, and a preview of their matches by defining `preview`. Plugins whose matches
+take a while to find can define `stream_matches` to show them in batches as they are found.
+
+Matches from different plugins are ranked against each other by a relevance score. Plugins that know how
+relevant their matches are can report it with `score`, otherwise it is guessed from the order of the
+matches and how well they fit the query.
 !*/
 
 pub use anyrun_interface::{self, HandleResult, Match, PluginInfo};
-pub use anyrun_macros::{get_matches, handler, info, init};
+pub use anyrun_macros::{
+    action_handler, actions, get_matches, handler, info, init, preview, score, stream_matches,
+};
+
+use abi_stable::{
+    std_types::{ROption, RString, RVec},
+    StableAbi,
+};
+
+/// A secondary action that can be run on a match, in addition to the default one run by the
+/// `handler`. Actions are provided with the optional `actions` and `action_handler` functions.
+#[repr(C)]
+#[derive(StableAbi, Clone, Debug)]
+pub struct MatchAction {
+    /// Identifies the action when it is passed to the `action_handler`
+    pub id: RString,
+    pub title: RString,
+    pub icon: ROption<RString>,
+    /// Keybind that runs the action directly, in the `Ctrl+Alt+Shift+<key>` format where
+    /// the key is a GDK key name. For example `Ctrl+Return`.
+    pub keybind: ROption<RString>,
+}
+
+/// The contents of the preview pane for a match, provided with the optional `preview` function
+#[repr(C)]
+#[derive(StableAbi, Clone, Debug)]
+pub enum Preview {
+    /// Plain text
+    Text(RString),
+    /// Text with Pango markup
+    Markup(RString),
+    /// Path to an image file, or the name of an icon
+    Image(RString),
+    /// Path to a file or a directory, rendered according to its type
+    File(RString),
+}
+
+/// Passed to `stream_matches`, sends batches of matches to be shown right away
+#[repr(C)]
+#[derive(StableAbi)]
+pub struct MatchSink {
+    context: *const (),
+    push: extern "C" fn(*const (), RVec<Match>) -> bool,
+}
+
+impl MatchSink {
+    /// Creates a sink calling `push` with `context` for every batch. Only used by the
+    /// provider, `context` has to stay valid for as long as the sink is used.
+    pub fn new(context: *const (), push: extern "C" fn(*const (), RVec<Match>) -> bool) -> Self {
+        Self { context, push }
+    }
+
+    /// Sends a batch of matches, which are shown after the ones sent before for the same
+    /// query. Returns `false` once the query is outdated, the plugin should stop then.
+    pub fn push(&self, matches: RVec<Match>) -> bool {
+        (self.push)(self.context, matches)
+    }
+}
+
+/// Signature of the function generated by the `actions` macro
+pub type ActionsFn = extern "C" fn(Match) -> RVec<MatchAction>;
+/// Signature of the function generated by the `action_handler` macro
+pub type HandleActionFn = extern "C" fn(Match, RString) -> HandleResult;
+/// Signature of the function generated by the `preview` macro
+pub type PreviewFn = extern "C" fn(Match) -> ROption<Preview>;
+/// Signature of the function generated by the `stream_matches` macro
+pub type StreamMatchesFn = extern "C" fn(RString, &MatchSink);
+/// Signature of the function generated by the `score` macro
+pub type ScoreFn = extern "C" fn(RString, Match) -> ROption<f64>;
+
+/// Symbol of the `ActionsFn` exported by the plugin, if it has actions
+pub const ACTIONS_SYMBOL: &[u8] = b"anyrun_internal_actions\0";
+/// Symbol of the `HandleActionFn` exported by the plugin, if it has actions
+pub const HANDLE_ACTION_SYMBOL: &[u8] = b"anyrun_internal_handle_action\0";
+/// Symbol of the `PreviewFn` exported by the plugin, if it has previews
+pub const PREVIEW_SYMBOL: &[u8] = b"anyrun_internal_preview\0";
+/// Symbol of the `StreamMatchesFn` exported by the plugin, if it streams its matches
+pub const STREAM_MATCHES_SYMBOL: &[u8] = b"anyrun_internal_stream_matches\0";
+/// Symbol of the `ScoreFn` exported by the plugin, if it reports the relevance of its matches
+pub const SCORE_SYMBOL: &[u8] = b"anyrun_internal_score\0";
 
 /*
 The macro to create a plugin, handles asynchronous execution of getting the matches and the boilerplate
@@ -1,4 +1,7 @@
-use std::io;
+use std::{
+    env, fmt, io,
+    path::{Path, PathBuf},
+};
 
 use anyrun_interface::{HandleResult, Match, PluginInfo, abi_stable::std_types::RVec};
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
@@ -11,6 +14,37 @@ use tokio::{
 pub const CONFIG_DIRS: &[&str] = &["/etc/xdg/anyrun", "/etc/anyrun"];
 pub const PLUGIN_PATHS: &[&str] = &["/usr/lib/anyrun", "/etc/anyrun/plugins"];
 
+/// The directories plugins are searched from, in order of priority
+pub fn plugin_dirs(user_dir: &Path) -> Vec<PathBuf> {
+    let mut plugin_dirs = vec![user_dir.join("plugins")];
+    if let Ok(path) = env::var("ANYRUN_PLUGINS") {
+        plugin_dirs.push(PathBuf::from(path));
+    }
+    plugin_dirs.extend(PLUGIN_PATHS.iter().map(PathBuf::from));
+    plugin_dirs
+}
+
+/// Resolves a plugin given either as an absolute path, a file name in one of the `dirs`,
+/// or a crate name such as `kidex` for `libkidex.so`
+pub fn find_plugin(name: &Path, dirs: &[PathBuf]) -> Option<PathBuf> {
+    if name.is_absolute() && name.exists() {
+        return Some(name.to_path_buf());
+    }
+    for dir in dirs {
+        let p = dir.join(name);
+        if p.exists() {
+            return Some(p);
+        }
+
+        let lib_name = format!("lib{}.so", name.to_string_lossy().replace('-', "_"));
+        let p = dir.join(lib_name);
+        if p.exists() {
+            return Some(p);
+        }
+    }
+    None
+}
+
 /// Requests from subscriber to provider
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Request {
@@ -27,7 +61,32 @@ pub enum Request {
     Handle {
         plugin: PluginInfo,
         selection: Match,
+        /// The `id` of the secondary action to run, or `None` for the default one
+        #[serde(default)]
+        action: Option<String>,
+    },
+    /// Handle several selections at once, each using the plugin that provided it.
+    /// Answered with a single `Response::HandledBatch`.
+    HandleBatch {
+        selections: Vec<(PluginInfo, Match)>,
     },
+    /// Get the preview of a match from the plugin that provided it
+    Preview {
+        plugin: PluginInfo,
+        selection: Match,
+    },
+    /// Load a plugin in addition to the ones passed on the command line, resolved the same
+    /// way. A plugin with the same name as a loaded one replaces it, so a rebuilt plugin can
+    /// be swapped in. Answered with `Response::PluginLoaded`.
+    LoadPlugin { path: PathBuf },
+    /// Stop using the plugin with the name. Answered with `Response::PluginUnloaded`.
+    UnloadPlugin { name: String },
+    /// Answered with `Response::Plugins`
+    ListPlugins,
+    /// Disconnect, for a provider started with `socket` that serves the next client. The
+    /// plugins are reset like with `Request::Reset` once the client is gone, so it doesn't
+    /// wait for it.
+    Detach,
     /// Close the provider
     Quit,
 }
@@ -42,17 +101,38 @@ pub enum Response {
         ///
         /// NOTE: In case of load failures, the vec may be shorter than the provided vec
         info: Vec<PluginInfo>,
-        // /// List of possible errors during intialization
-        // ///
-        // /// TODO: Perhaps unnecessary
-        // errors: Vec<String>,
+        /// The plugins that failed to load, in the same order as the paths
+        #[serde(default)]
+        errors: Vec<PluginError>,
     },
-    /// A response to a `Request::Query`. One of these will be received for each plugin per query.
+    /// A response to a `Request::Query`. One of these will be received for each plugin per query,
+    /// or several with `partial` set for plugins that stream their matches.
     Matches {
         /// The plugin these matches belong to
         plugin: PluginInfo,
         /// The matches
         matches: RVec<Match>,
+        /// Relevance of each match in the range `0.0..=1.0`, normalized per plugin.
+        /// Has the same length and order as `matches`.
+        #[serde(default)]
+        scores: Vec<f64>,
+        /// The secondary actions of each match, in the same order as `matches`
+        #[serde(default)]
+        actions: Vec<Vec<MatchAction>>,
+        /// The text of the `Request::Query` these matches belong to
+        #[serde(default)]
+        query: Option<String>,
+        /// Whether these are added after the matches received before for the same plugin and
+        /// query, instead of replacing them. Used by plugins that stream their matches.
+        #[serde(default)]
+        append: bool,
+        /// Whether more matches of the plugin follow for the same query
+        #[serde(default)]
+        partial: bool,
+        /// Whether the plugin has repeatedly been too slow to answer lately. Its matches are
+        /// ranked lower already, clients may also show it after the other plugins.
+        #[serde(default)]
+        slow: bool,
     },
     /// A response to a `Request::Handle`
     Handled {
@@ -61,6 +141,114 @@ pub enum Response {
         /// The result provided by the plugin
         result: HandleResult,
     },
+    /// A response to a `Request::HandleBatch`
+    HandledBatch {
+        /// The plugin and the result of each selection that could be handled, in the
+        /// same order as the selections
+        results: Vec<(PluginInfo, HandleResult)>,
+    },
+    /// Sent after the `Matches` of a plugin that didn't answer a query before its deadline.
+    /// Those `Matches` are empty. A plugin still busy with an earlier query is called with the
+    /// latest one once it has returned.
+    PluginTimedOut {
+        plugin: PluginInfo,
+        /// Milliseconds the plugin had been working on the query
+        elapsed_ms: u64,
+    },
+    /// Sent when the process hosting a plugin crashed, only when the provider runs with
+    /// `--isolate`. Its matches are gone, and it answers queries again once it is restarted.
+    /// A `Request::Handle` it was busy with is answered with `HandleResult::Close`.
+    PluginCrashed { plugin: PluginInfo },
+    /// A response to a `Request::LoadPlugin`. Like after `Response::PluginUnloaded`, the
+    /// matches for a query in progress may not arrive anymore, so the query is sent again.
+    PluginLoaded {
+        /// The plugin as reported by itself, or why it could not be loaded
+        result: Result<PluginInfo, PluginError>,
+    },
+    /// A response to a `Request::UnloadPlugin`
+    PluginUnloaded {
+        name: String,
+        /// Whether a plugin with the name was loaded
+        unloaded: bool,
+    },
+    /// A response to a `Request::ListPlugins`
+    Plugins { plugins: Vec<LoadedPlugin> },
+    /// A response to a `Request::Preview`
+    Preview {
+        plugin: PluginInfo,
+        /// The match the preview was requested for, to tell apart outdated previews
+        selection: Match,
+        /// The preview, or `None` if the plugin has nothing to preview for the match
+        preview: Option<Preview>,
+    },
+}
+
+/// A plugin used by the provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoadedPlugin {
+    pub info: PluginInfo,
+    /// The path of the plugin as it was provided
+    pub path: PathBuf,
+}
+
+/// A plugin that failed to load
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginError {
+    /// The path of the plugin as it was provided
+    pub path: PathBuf,
+    pub stage: LoadStage,
+    /// What went wrong, including the details of an ABI mismatch
+    pub message: String,
+}
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stage = match self.stage {
+            LoadStage::Find => "not found",
+            LoadStage::Open => "could not be opened",
+            LoadStage::Load => "could not be loaded",
+        };
+        write!(
+            f,
+            "Plugin `{}` {stage}: {}",
+            self.path.display(),
+            self.message
+        )
+    }
+}
+
+/// The step of loading a plugin that failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LoadStage {
+    /// Looking the plugin up in the plugin directories
+    Find,
+    /// Opening the library and reading its header
+    Open,
+    /// Loading the plugin module, which fails if it was built against an incompatible
+    /// version of the plugin interface
+    Load,
+}
+
+/// A secondary action of a match, as provided by the plugin
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchAction {
+    /// Identifies the action in `Request::Handle`
+    pub id: String,
+    pub title: String,
+    pub icon: Option<String>,
+    /// Keybind that runs the action directly, in the `Ctrl+Alt+Shift+<key>` format
+    pub keybind: Option<String>,
+}
+
+/// The contents of the preview pane, as provided by the plugin
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Preview {
+    Text(String),
+    Markup(String),
+    /// Path to an image file, or the name of an icon
+    Image(String),
+    /// Path to a file or a directory
+    File(String),
 }
 
 /// Possible errors reported by the provider
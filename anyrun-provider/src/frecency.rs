@@ -0,0 +1,377 @@
+use anyrun_interface::{Match, abi_stable::std_types::ROption};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    env, fs, io,
+    path::{Path, PathBuf},
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The frecency at which a match gets half of the maximum boost, equal to a single
+/// selection within the last hour
+const HALF_BOOST: f64 = 4.0;
+/// Maximum amount of entries remembered per plugin, the least frecent are evicted first
+const MAX_ENTRIES: usize = 500;
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    /// How many times the match has been selected
+    pub count: u32,
+    /// Unix timestamp of the last selection
+    pub last_used: u64,
+}
+
+impl Entry {
+    /// Frequency weighted by how recently the match was selected
+    pub fn frecency(&self, now: u64) -> f64 {
+        let age = now.saturating_sub(self.last_used);
+        let recency = if age < HOUR {
+            4.0
+        } else if age < DAY {
+            2.0
+        } else if age < WEEK {
+            0.5
+        } else {
+            0.25
+        };
+
+        self.count as f64 * recency
+    }
+}
+
+/// Persistent record of the selections made through the provider, keyed by plugin name
+/// and the identity of the selected match. Several providers may share the store, so it
+/// is read again whenever another one has changed it.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Frecency {
+    entries: HashMap<String, HashMap<String, Entry>>,
+    #[serde(skip)]
+    disabled: Vec<String>,
+    #[serde(skip)]
+    path: PathBuf,
+    /// The version of the file the entries were last read from or written to
+    #[serde(skip)]
+    version: Option<Version>,
+}
+
+/// Changes whenever the file is written
+type Version = (SystemTime, u64);
+
+impl Frecency {
+    /// Loads the store from the state directory, starting with an empty one if it doesn't
+    /// exist or can't be read.
+    pub fn load(disabled: Vec<String>) -> Self {
+        Self::load_from(state_dir().join("frecency.json"), disabled)
+    }
+
+    fn load_from(path: PathBuf, disabled: Vec<String>) -> Self {
+        let mut frecency = Self {
+            disabled,
+            path,
+            ..Self::default()
+        };
+        frecency.reload();
+        frecency
+    }
+
+    /// Reads the store again if it was written since, by this or another provider
+    pub fn refresh(&mut self) {
+        if version(&self.path) != self.version {
+            self.reload();
+        }
+    }
+
+    fn reload(&mut self) {
+        self.version = version(&self.path);
+        self.entries = match fs::read(&self.path) {
+            Ok(content) => match serde_json::from_slice::<Self>(&content) {
+                Ok(store) => store.entries,
+                Err(why) => {
+                    eprintln!(
+                        "[anyrun-provider] Failed to parse frecency store, starting over: {why}"
+                    );
+                    HashMap::new()
+                }
+            },
+            Err(_) => HashMap::new(),
+        };
+    }
+
+    /// Writes the store to a temporary file first, so other providers never read it half
+    /// written. Call `refresh` before making the changes, so the ones of other providers
+    /// are kept.
+    pub fn save(&mut self) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(format!(".{}.tmp", process::id()));
+        fs::write(&temp, serde_json::to_vec(self).map_err(io::Error::other)?)?;
+        fs::rename(&temp, &self.path)?;
+        self.version = version(&self.path);
+        Ok(())
+    }
+
+    fn is_enabled(&self, plugin: &str) -> bool {
+        !self.disabled.iter().any(|name| name == plugin)
+    }
+
+    /// Remembers a selection made from the matches of `plugin`
+    pub fn record(&mut self, plugin: &str, selection: &Match) {
+        if !self.is_enabled(plugin) {
+            return;
+        }
+
+        let now = now();
+        let entries = self.entries.entry(plugin.to_string()).or_default();
+        let entry = entries.entry(identity(selection)).or_insert(Entry {
+            count: 0,
+            last_used: now,
+        });
+        entry.count = entry.count.saturating_add(1);
+        entry.last_used = now;
+
+        if entries.len() > MAX_ENTRIES {
+            if let Some(key) = entries
+                .iter()
+                .min_by(|a, b| a.1.frecency(now).total_cmp(&b.1.frecency(now)))
+                .map(|(key, _)| key.clone())
+            {
+                entries.remove(&key);
+            }
+        }
+    }
+
+    /// Boosts the scores of previously selected matches and moves them to the top, the most
+    /// frecent first. Matches that have never been selected keep their relative order.
+    pub fn rerank<T>(&self, plugin: &str, results: &mut [(Match, f64, T)]) {
+        let Some(entries) = self.entries.get(plugin).filter(|_| self.is_enabled(plugin)) else {
+            return;
+        };
+
+        let now = now();
+        let frecency = |_match: &Match| {
+            entries
+                .get(&identity(_match))
+                .map_or(0.0, |entry| entry.frecency(now))
+        };
+        let mut boosted = false;
+        for (_match, score, _) in results.iter_mut() {
+            let frecency = frecency(_match);
+            if frecency > 0.0 {
+                *score += (1.0 - *score) * frecency / (frecency + HALF_BOOST);
+                boosted = true;
+            }
+        }
+
+        // Stable, and only by frecency so the scores guessed for the other matches don't
+        // override the order the plugin gave them
+        if boosted {
+            results.sort_by(|a, b| frecency(&b.0).total_cmp(&frecency(&a.0)));
+        }
+    }
+
+    /// All remembered entries of a plugin, or of all plugins if `plugin` is `None`,
+    /// ordered from most to least frecent
+    pub fn list(&self, plugin: Option<&str>) -> Vec<(&str, &str, &Entry, f64)> {
+        let now = now();
+        let mut list = self
+            .entries
+            .iter()
+            .filter(|(name, _)| plugin.is_none_or(|plugin| plugin == name.as_str()))
+            .flat_map(|(name, entries)| {
+                entries.iter().map(move |(key, entry)| {
+                    (name.as_str(), key.as_str(), entry, entry.frecency(now))
+                })
+            })
+            .collect::<Vec<_>>();
+        list.sort_by(|a, b| b.3.total_cmp(&a.3));
+        list
+    }
+
+    /// Forgets the entries of a plugin, or of all plugins if `plugin` is `None`
+    pub fn reset(&mut self, plugin: Option<&str>) {
+        match plugin {
+            Some(plugin) => {
+                self.entries.remove(plugin);
+            }
+            None => self.entries.clear(),
+        }
+    }
+}
+
+/// A stable identity for a match. Plugin provided IDs are often just indices, so the
+/// displayed contents are used instead.
+fn identity(_match: &Match) -> String {
+    match &_match.description {
+        ROption::RSome(desc) => format!("{}\n{desc}", _match.title),
+        ROption::RNone => _match.title.to_string(),
+    }
+}
+
+fn version(path: &Path) -> Option<Version> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+fn state_dir() -> PathBuf {
+    env::var("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| {
+            let mut p = PathBuf::from(env::var("HOME").unwrap_or_else(|_| ".".into()));
+            p.push(".local/state");
+            p
+        })
+        .join("anyrun")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results(titles: &[&str]) -> Vec<(Match, f64, ())> {
+        titles
+            .iter()
+            .enumerate()
+            .map(|(i, title)| {
+                let _match = Match {
+                    title: (*title).into(),
+                    description: ROption::RNone,
+                    use_pango: false,
+                    icon: ROption::RNone,
+                    id: ROption::RNone,
+                };
+                (_match, 1.0 - i as f64 / titles.len() as f64, ())
+            })
+            .collect()
+    }
+
+    fn titles(results: &[(Match, f64, ())]) -> Vec<&str> {
+        results
+            .iter()
+            .map(|(_match, _, _)| _match.title.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn selected_matches_move_up() {
+        let mut frecency = Frecency::default();
+        let mut ranked = results(&["a", "b", "c", "d"]);
+        frecency.record("plugin", &ranked[2].0);
+        frecency.record("plugin", &ranked[3].0);
+        frecency.record("plugin", &ranked[3].0);
+        let score = ranked[3].1;
+
+        frecency.rerank("plugin", &mut ranked);
+        assert_eq!(titles(&ranked), ["d", "c", "a", "b"]);
+        assert!(ranked[0].1 > score);
+    }
+
+    #[test]
+    fn unselected_matches_keep_their_order() {
+        let mut frecency = Frecency::default();
+        // Scores that don't follow the order, as guessed from how well the matches fit
+        let mut ranked = results(&["a", "b", "c", "d", "e"]);
+        ranked[1].1 = 0.1;
+        ranked[3].1 = 0.9;
+        frecency.record("plugin", &ranked[2].0);
+
+        frecency.rerank("plugin", &mut ranked);
+        assert_eq!(titles(&ranked), ["c", "a", "b", "d", "e"]);
+    }
+
+    #[test]
+    fn other_and_disabled_plugins_are_untouched() {
+        let mut frecency = Frecency::default();
+        let mut ranked = results(&["a", "b"]);
+        frecency.record("plugin", &ranked[1].0);
+
+        frecency.rerank("other", &mut ranked);
+        assert_eq!(titles(&ranked), ["a", "b"]);
+
+        frecency.disabled = vec!["plugin".to_string()];
+        frecency.rerank("plugin", &mut ranked);
+        assert_eq!(titles(&ranked), ["a", "b"]);
+    }
+
+    #[test]
+    fn frecency_decays() {
+        let now = 100 * WEEK;
+        let entry = |age| Entry {
+            count: 2,
+            last_used: now - age,
+        };
+        assert_eq!(entry(0).frecency(now), 8.0);
+        assert_eq!(entry(2 * HOUR).frecency(now), 4.0);
+        assert_eq!(entry(2 * DAY).frecency(now), 1.0);
+        assert_eq!(entry(2 * WEEK).frecency(now), 0.5);
+        // Selections in the future, from a clock that was set back, count as recent
+        assert_eq!(entry(0).frecency(now - HOUR), 8.0);
+    }
+
+    fn store_path(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("anyrun-frecency-{name}-{}.json", process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn selections_of_other_providers_are_kept() {
+        let path = store_path("merge");
+        let mut first = Frecency::load_from(path.clone(), Vec::new());
+        let mut second = Frecency::load_from(path.clone(), Vec::new());
+
+        first.record("plugin", &results(&["a"])[0].0);
+        first.save().unwrap();
+        second.refresh();
+        second.record("plugin", &results(&["b"])[0].0);
+        second.save().unwrap();
+
+        let entries = &Frecency::load_from(path.clone(), Vec::new()).entries["plugin"];
+        assert!(entries.contains_key("a") && entries.contains_key("b"));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn resets_reach_running_providers() {
+        let path = store_path("reset");
+        let mut running = Frecency::load_from(path.clone(), Vec::new());
+        running.record("plugin", &results(&["a"])[0].0);
+        running.save().unwrap();
+
+        let mut command = Frecency::load_from(path.clone(), Vec::new());
+        command.reset(None);
+        command.save().unwrap();
+
+        running.refresh();
+        assert!(running.entries.is_empty());
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn least_frecent_entries_are_evicted() {
+        let mut frecency = Frecency::default();
+        let ranked = results(&["kept"]);
+        frecency.record("plugin", &ranked[0].0);
+        frecency.record("plugin", &ranked[0].0);
+        for i in 0..MAX_ENTRIES {
+            frecency.record("plugin", &results(&[i.to_string().as_str()])[0].0);
+        }
+
+        let entries = &frecency.entries["plugin"];
+        assert_eq!(entries.len(), MAX_ENTRIES);
+        assert!(entries.contains_key("kept"));
+    }
+}
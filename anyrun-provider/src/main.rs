@@ -1,15 +1,67 @@
-use anyrun_interface::{Match, PluginInfo, PluginRef, abi_stable};
-use anyrun_provider_ipc::{CONFIG_DIRS, PLUGIN_PATHS, Request, Response, Socket};
+use anyrun_interface::{
+    HandleResult, Match, PluginInfo, PluginRef,
+    abi_stable::{self, library::RawLibrary, std_types::RVec},
+};
+use anyrun_plugin::{
+    ACTIONS_SYMBOL, ActionsFn, HANDLE_ACTION_SYMBOL, HandleActionFn, MatchSink, PREVIEW_SYMBOL,
+    PreviewFn, SCORE_SYMBOL, STREAM_MATCHES_SYMBOL, ScoreFn, StreamMatchesFn,
+};
+use anyrun_provider_ipc::{
+    CONFIG_DIRS, LoadStage, LoadedPlugin, MatchAction, PluginError, Preview, Request, Response,
+    Socket, find_plugin, plugin_dirs,
+};
 use clap::{Parser, Subcommand};
 use futures::stream::{FuturesUnordered, StreamExt};
-use std::{collections::HashMap, env, io, path::PathBuf, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs, io,
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        mpsc as std_mpsc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 use tokio::{
     net::{UnixListener, UnixStream},
-    task::{AbortHandle, JoinHandle},
+    sync::{
+        Notify,
+        mpsc::{self, UnboundedSender},
+    },
+    task::JoinHandle,
 };
 
+mod frecency;
+mod relevance;
+mod supervisor;
+
+use frecency::Frecency;
+use supervisor::Supervisor;
+
+/// Tells apart the copies of plugins that are loaded again
+static COPY_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Cap on the strikes of a plugin, so a plugin that was slow for a while recovers soon
+/// after it answers in time again
+const MAX_STRIKES: u32 = 4;
+/// Strikes from which a plugin is reported as slow, to be shown after the other plugins
+const SLOW_STRIKES: u32 = 2;
+
 // Định nghĩa alias để code gọn gàng hơn
-type PluginQueryResult = (abi_stable::std_types::RVec<Match>, usize);
+type PluginQueryResult = (QueryOutcome, usize);
+/// A match along with its relevance score and secondary actions
+type RankedMatch = (Match, f64, Vec<MatchAction>);
+/// The plugin index and match a preview was requested for, along with the preview
+type PreviewResult = (usize, Match, Option<Preview>);
+/// A batch pushed by a plugin streaming its matches, with the plugin index and the generation
+/// of the query it belongs to
+type StreamedBatch = (usize, u64, Vec<RankedMatch>);
+/// The matches of a plugin split into the fields of `Response::Matches`
+type MatchesFields = (RVec<Match>, Vec<f64>, Vec<Vec<MatchAction>>);
 
 #[derive(Parser)]
 #[command(version)]
@@ -20,12 +72,68 @@ struct Args {
     plugins: Vec<PathBuf>,
     #[arg(short, long)]
     config_dir: Option<String>,
+    /// Don't boost frequently and recently selected matches
+    #[arg(long)]
+    no_frecency: bool,
+    /// Names of plugins whose matches are not boosted by frecency
+    #[arg(long)]
+    frecency_exclude: Vec<String>,
+    /// Maximum amount of matches returned by a plugin, as `NAME=COUNT`
+    #[arg(long, value_parser = parse_plugin_value::<usize>)]
+    max_entries: Vec<(String, usize)>,
+    /// Milliseconds a plugin has to answer a query, as `NAME=MS`. 0 waits indefinitely.
+    #[arg(long, value_parser = parse_plugin_value::<u64>)]
+    timeout: Vec<(String, u64)>,
+    /// Milliseconds plugins without a `--timeout` have to answer a query
+    #[arg(long)]
+    default_timeout: Option<u64>,
+    /// Names of plugins that are not queried when the query is empty
+    #[arg(long)]
+    skip_empty_query: Vec<String>,
+    /// Run every plugin in a process of its own, so a plugin crashing doesn't take the
+    /// others down with it
+    #[arg(long)]
+    isolate: bool,
+    /// Plugins sharing a process with `--isolate`, as a comma separated list of the paths
+    /// given with `-p`
+    #[arg(long)]
+    isolate_group: Vec<String>,
+}
+
+fn parse_plugin_value<T: FromStr>(arg: &str) -> Result<(String, T), String>
+where
+    T::Err: std::fmt::Display,
+{
+    let (name, value) = arg
+        .rsplit_once('=')
+        .ok_or_else(|| format!("expected NAME=VALUE, got `{arg}`"))?;
+    let value = value
+        .parse()
+        .map_err(|why| format!("invalid value `{value}`: {why}"))?;
+    Ok((name.to_string(), value))
 }
 
 #[derive(Clone, Subcommand)]
 enum Command {
-    Socket { path: PathBuf },
-    ConnectTo { path: PathBuf },
+    Socket {
+        path: PathBuf,
+    },
+    ConnectTo {
+        path: PathBuf,
+    },
+    /// Inspect or reset the selection history used for frecency
+    Frecency {
+        #[command(subcommand)]
+        command: FrecencyCommand,
+    },
+}
+
+#[derive(Clone, Subcommand)]
+enum FrecencyCommand {
+    /// List the remembered selections, most frecent first
+    List { plugin: Option<String> },
+    /// Forget the remembered selections of a plugin, or of all plugins
+    Reset { plugin: Option<String> },
 }
 
 enum WorkerResult {
@@ -33,21 +141,157 @@ enum WorkerResult {
     Continue,
 }
 
+/// What became of querying a plugin
+enum QueryOutcome {
+    Matches(Vec<RankedMatch>),
+    /// The plugin didn't answer before its deadline
+    TimedOut(Duration),
+}
+
 struct PluginState {
     plugin: PluginRef,
     info: PluginInfo,
+    /// The path of the plugin as it was provided
+    path: PathBuf,
+    /// Optional functions providing secondary actions
+    actions: Option<(ActionsFn, HandleActionFn)>,
+    /// Optional function providing previews of matches
+    preview: Option<PreviewFn>,
+    /// Optional function finding the matches in batches, used instead of `get_matches`
+    stream: Option<StreamMatchesFn>,
+    /// Optional function reporting the relevance of matches
+    score: Option<ScoreFn>,
+    /// The library the optional functions were looked up in, which has to stay open while
+    /// they are used
+    _library: Option<RawLibrary>,
+    /// Maximum amount of matches sent to the client
+    max_entries: Option<usize>,
+    /// Whether the plugin is queried when the query is empty
+    empty_query: bool,
+    /// How long the plugin has to answer a query
+    timeout: Option<Duration>,
+    /// When the call to the plugin that is still running started. A plugin that is stuck
+    /// isn't called again until it returns.
+    running_since: Arc<Mutex<Option<Instant>>>,
+    /// Notified when the running call returns
+    idle: Arc<Notify>,
+    /// Recent timeouts, lowering the relevance of the matches of plugins that are often
+    /// slow. Each timely answer forgives one.
+    strikes: u32,
 }
 
 struct State {
     plugins: Vec<PluginState>,
     plugin_map: HashMap<String, usize>,
     config_dir: Arc<str>,
+    frecency: Option<Frecency>,
+    /// The plugins that failed to load, reported to every client
+    errors: Vec<PluginError>,
+    plugin_dirs: Vec<PathBuf>,
+    /// The settings of the plugins from the command line, also applied to the plugins loaded
+    /// with `Request::LoadPlugin`
+    settings: PluginSettings,
+    /// The libraries loaded so far, which have to be copied to be loaded again
+    libraries: HashSet<PathBuf>,
+}
+
+struct PluginSettings {
+    max_entries: Vec<(String, usize)>,
+    timeout: Vec<(String, u64)>,
+    default_timeout: Option<u64>,
+    skip_empty_query: Vec<String>,
+}
+
+impl State {
+    /// Runs the `init` of every plugin again, dropping the state they kept for the client.
+    /// Some plugins take a while, so it runs on a blocking thread.
+    async fn reset(&self) {
+        let inits: Vec<_> = self.plugins.iter().map(|p| p.plugin.init()).collect();
+        let config_dir = Arc::clone(&self.config_dir);
+        let _ = tokio::task::spawn_blocking(move || {
+            for init in inits {
+                init(config_dir.as_ref().into());
+            }
+        })
+        .await;
+    }
+
+    /// Loads and initializes the plugin
+    fn load(&mut self, plugin_path: &Path) -> Result<PluginState, PluginError> {
+        let (plugin, lib) = load_plugin(plugin_path, &self.plugin_dirs, &mut self.libraries)?;
+        plugin.init()(self.config_dir.as_ref().into());
+        let info = plugin.info()();
+        let name = info.name.as_str();
+        let settings = &self.settings;
+        Ok(PluginState {
+            plugin,
+            path: plugin_path.to_path_buf(),
+            actions: lib.as_ref().and_then(load_actions),
+            preview: lib.as_ref().and_then(load_preview),
+            stream: lib.as_ref().and_then(load_stream),
+            score: lib.as_ref().and_then(load_score),
+            _library: lib,
+            max_entries: settings
+                .max_entries
+                .iter()
+                .find(|(plugin, _)| plugin == name)
+                .map(|(_, count)| *count),
+            empty_query: !settings
+                .skip_empty_query
+                .iter()
+                .any(|plugin| plugin == name),
+            timeout: settings
+                .timeout
+                .iter()
+                .find(|(plugin, _)| plugin == name)
+                .map(|(_, ms)| *ms)
+                .or(settings.default_timeout)
+                .filter(|ms| *ms > 0)
+                .map(Duration::from_millis),
+            running_since: Arc::new(Mutex::new(None)),
+            idle: Arc::new(Notify::new()),
+            strikes: 0,
+            info,
+        })
+    }
+
+    /// Adds the plugin, replacing the one with the same name if there is one
+    fn add(&mut self, plugin: PluginState) -> PluginInfo {
+        let info = plugin.info.clone();
+        match self.plugin_map.get(info.name.as_str()) {
+            Some(&idx) => self.plugins[idx] = plugin,
+            None => {
+                self.plugin_map
+                    .insert(info.name.to_string(), self.plugins.len());
+                self.plugins.push(plugin);
+            }
+        }
+        info
+    }
+
+    /// Removes the plugin with the name, returns whether there was one
+    fn remove(&mut self, name: &str) -> bool {
+        let Some(idx) = self.plugin_map.remove(name) else {
+            return false;
+        };
+        self.plugins.remove(idx);
+        for other in self.plugin_map.values_mut() {
+            if *other > idx {
+                *other -= 1;
+            }
+        }
+        true
+    }
 }
 
 #[tokio::main]
 async fn main() -> io::Result<()> {
     let args = Args::parse();
 
+    if let Command::Frecency { command } = &args.command {
+        return frecency_command(command);
+    }
+
     let user_dir = env::var("XDG_CONFIG_HOME")
         .map(PathBuf::from)
         .unwrap_or_else(|_| {
@@ -57,7 +301,7 @@ async fn main() -> io::Result<()> {
         })
         .join("anyrun");
 
-    let config_dir: Arc<str> = args.config_dir.map(Into::into).unwrap_or_else(|| {
+    let config_dir: Arc<str> = args.config_dir.clone().map(Into::into).unwrap_or_else(|| {
         if user_dir.exists() {
             user_dir.to_string_lossy().into()
         } else {
@@ -69,28 +313,52 @@ async fn main() -> io::Result<()> {
         }
     });
 
-    let mut plugin_dirs = vec![user_dir.join("plugins")];
-    if let Ok(path) = env::var("ANYRUN_PLUGINS") {
-        plugin_dirs.push(PathBuf::from(path));
+    if args.isolate {
+        let mut supervisor = Supervisor::start(&args, &config_dir).await;
+        match args.command {
+            Command::Socket { path } => {
+                let _ = std::fs::remove_file(&path);
+                let listener = UnixListener::bind(path)?;
+                loop {
+                    let (stream, _) = listener.accept().await?;
+                    if let WorkerResult::Quit = supervisor::worker(stream, &mut supervisor).await? {
+                        break;
+                    }
+                }
+            }
+            Command::ConnectTo { path } => {
+                let stream = UnixStream::connect(path).await?;
+                supervisor::worker(stream, &mut supervisor).await?;
+            }
+            Command::Frecency { .. } => unreachable!(),
+        }
+        return Ok(());
     }
-    plugin_dirs.extend(PLUGIN_PATHS.iter().map(PathBuf::from));
 
     let mut state = State {
         plugins: Vec::with_capacity(args.plugins.len()),
         plugin_map: HashMap::with_capacity(args.plugins.len()),
         config_dir,
+        frecency: (!args.no_frecency).then(|| Frecency::load(args.frecency_exclude)),
+        errors: Vec::new(),
+        plugin_dirs: plugin_dirs(&user_dir),
+        settings: PluginSettings {
+            max_entries: args.max_entries,
+            timeout: args.timeout,
+            default_timeout: args.default_timeout,
+            skip_empty_query: args.skip_empty_query,
+        },
+        libraries: HashSet::new(),
     };
 
     for plugin_path in &args.plugins {
-        if let Some(path) = find_plugin(plugin_path, &plugin_dirs) {
-            if let Ok(header) = abi_stable::library::lib_header_from_path(&path) {
-                if let Ok(plugin) = header.init_root_module::<PluginRef>() {
-                    plugin.init()(state.config_dir.as_ref().into());
-                    let info = plugin.info()();
-                    let idx = state.plugins.len();
-                    state.plugin_map.insert(info.name.to_string(), idx);
-                    state.plugins.push(PluginState { plugin, info });
-                }
+        match state.load(plugin_path) {
+            Ok(plugin) => {
+                state.add(plugin);
+            }
+            Err(error) => {
+                eprintln!("[anyrun-provider] {error}");
+                state.errors.push(error);
             }
         }
     }
@@ -110,6 +378,28 @@ async fn main() -> io::Result<()> {
             let stream = UnixStream::connect(path).await?;
             worker(stream, &mut state).await?;
         }
+        Command::Frecency { .. } => unreachable!(),
+    }
+    Ok(())
+}
+
+fn frecency_command(command: &FrecencyCommand) -> io::Result<()> {
+    let mut frecency = Frecency::load(Vec::new());
+
+    match command {
+        FrecencyCommand::List { plugin } => {
+            for (plugin, key, entry, score) in frecency.list(plugin.as_deref()) {
+                println!(
+                    "{score:>8.2} {:>5} {plugin}: {}",
+                    entry.count,
+                    key.replace('\n', " - ")
+                );
+            }
+        }
+        FrecencyCommand::Reset { plugin } => {
+            frecency.reset(plugin.as_deref());
+            frecency.save()?;
+        }
     }
     Ok(())
 }
@@ -118,20 +408,119 @@ async fn worker(stream: UnixStream, state: &mut State) -> io::Result<WorkerResul
     let mut socket = Socket::new(stream);
 
     let plugin_infos: Vec<PluginInfo> = state.plugins.iter().map(|p| p.info.clone()).collect();
-    socket.send(&Response::Ready { info: plugin_infos }).await?;
+    socket
+        .send(&Response::Ready {
+            info: plugin_infos,
+            errors: state.errors.clone(),
+        })
+        .await?;
 
     let mut pending_results: FuturesUnordered<JoinHandle<PluginQueryResult>> =
         FuturesUnordered::new();
-    let mut abort_handles: Vec<AbortHandle> = Vec::new();
+    // Only the preview of the latest selection is of interest
+    let mut pending_preview: FuturesUnordered<JoinHandle<PreviewResult>> = FuturesUnordered::new();
+
+    // Batches of streaming plugins, dropped once their query is outdated
+    let (batch_tx, mut batch_rx) = mpsc::unbounded_channel::<StreamedBatch>();
+    let generation = Arc::new(AtomicU64::new(0));
+    let mut query: Arc<str> = "".into();
+    // Amount of matches sent for the current query per plugin, `None` once it is done
+    let mut streamed: Vec<Option<usize>> = vec![None; state.plugins.len()];
+    let mut detached = false;
 
     loop {
         tokio::select! {
+            // Batches come first, so they are sent before the plugin is done with the query
+            biased;
+
+            Some((idx, batch_generation, mut results)) = batch_rx.recv() => {
+                if batch_generation != generation.load(Ordering::Relaxed) {
+                    continue;
+                }
+                let (Some(p_state), Some(Some(sent))) =
+                    (state.plugins.get(idx), streamed.get(idx).copied())
+                else {
+                    continue;
+                };
+                let limit = p_state.max_entries.map(|max| max.saturating_sub(sent));
+                if limit == Some(0) {
+                    continue;
+                }
+                if p_state.strikes > 0 {
+                    let penalty = 1.0 / (1 + p_state.strikes) as f64;
+                    for (_, score, _) in &mut results {
+                        *score *= penalty;
+                    }
+                }
+                let (matches, scores, actions) =
+                    rank_matches(&p_state.info, results, state.frecency.as_ref(), limit);
+                streamed[idx] = Some(sent + matches.len());
+                socket.send(&Response::Matches {
+                    plugin: p_state.info.clone(),
+                    matches,
+                    scores,
+                    actions,
+                    query: Some(query.to_string()),
+                    append: sent > 0,
+                    partial: true,
+                    slow: p_state.strikes >= SLOW_STRIKES,
+                }).await?;
+            }
+
             Some(join_result) = pending_results.next() => {
-                if let Ok((matches, idx)) = join_result {
-                    if let Some(p_state) = state.plugins.get(idx) {
+                if let Ok((outcome, idx)) = join_result {
+                    if let Some(p_state) = state.plugins.get_mut(idx) {
+                        let (results, timed_out) = match outcome {
+                            QueryOutcome::Matches(mut results) => {
+                                if p_state.strikes > 0 {
+                                    let penalty = 1.0 / (1 + p_state.strikes) as f64;
+                                    for (_, score, _) in &mut results {
+                                        *score *= penalty;
+                                    }
+                                    p_state.strikes -= 1;
+                                }
+                                (results, None)
+                            }
+                            QueryOutcome::TimedOut(elapsed) => {
+                                p_state.strikes = (p_state.strikes + 1).min(MAX_STRIKES);
+                                (Vec::new(), Some(elapsed))
+                            }
+                        };
+                        // Streamed matches are kept, and only added to if it answered in time
+                        let sent = streamed[idx].take().unwrap_or_default();
+                        let (matches, scores, actions) = rank_matches(
+                            &p_state.info,
+                            results,
+                            state.frecency.as_ref(),
+                            p_state.max_entries.map(|max| max.saturating_sub(sent)),
+                        );
                         socket.send(&Response::Matches {
                             plugin: p_state.info.clone(),
                             matches,
+                            scores,
+                            actions,
+                            query: Some(query.to_string()),
+                            append: sent > 0,
+                            partial: false,
+                            slow: p_state.strikes >= SLOW_STRIKES,
+                        }).await?;
+                        if let Some(elapsed) = timed_out {
+                            socket.send(&Response::PluginTimedOut {
+                                plugin: p_state.info.clone(),
+                                elapsed_ms: elapsed.as_millis() as u64,
+                            }).await?;
+                        }
+                    }
+                }
+            }
+
+            Some(join_result) = pending_preview.next() => {
+                if let Ok((idx, selection, preview)) = join_result {
+                    if let Some(p_state) = state.plugins.get(idx) {
+                        socket.send(&Response::Preview {
+                            plugin: p_state.info.clone(),
+                            selection,
+                            preview,
                         }).await?;
                     }
                 }
@@ -147,60 +536,562 @@ async fn worker(stream: UnixStream, state: &mut State) -> io::Result<WorkerResul
 
                 match request {
                     Request::Query { text } => {
-                        for handle in abort_handles.drain(..) {
-                            handle.abort();
+                        abort_all(&mut pending_results);
+                        // Picks up selections and resets from other providers
+                        if let Some(frecency) = &mut state.frecency {
+                            frecency.refresh();
                         }
-                        pending_results.clear();
 
-                        let query: Arc<str> = text.into();
+                        query = text.into();
+                        let query_generation = generation.fetch_add(1, Ordering::Relaxed) + 1;
+                        streamed.fill(Some(0));
                         for (idx, p_state) in state.plugins.iter().enumerate() {
+                            if !p_state.empty_query && query.trim().is_empty() {
+                                pending_results.push(tokio::spawn(async move {
+                                    (QueryOutcome::Matches(Vec::new()), idx)
+                                }));
+                                continue;
+                            }
+
                             let plugin_fn = p_state.plugin.get_matches();
+                            let stream_fn = p_state.stream;
+                            let actions_fn = p_state.actions.map(|(actions, _)| actions);
+                            let score_fn = p_state.score;
                             let q = Arc::clone(&query);
+                            let context = StreamContext {
+                                idx,
+                                generation: query_generation,
+                                current: Arc::clone(&generation),
+                                query: Arc::clone(&query),
+                                actions: actions_fn,
+                                score: score_fn,
+                                tx: batch_tx.clone(),
+                            };
+                            let timeout = p_state.timeout;
+                            let running_since = Arc::clone(&p_state.running_since);
+                            let idle = Arc::clone(&p_state.idle);
 
-                            let handle = tokio::task::spawn_blocking(move || {
-                                (plugin_fn(q.as_ref().into()), idx)
-                            });
-
-                            abort_handles.push(handle.abort_handle());
-                            pending_results.push(handle);
+                            let call = async move {
+                                // A plugin still busy with an earlier query is called with the
+                                // latest one once it returns. Each query aborts the task
+                                // waiting here for the one before.
+                                loop {
+                                    {
+                                        let mut running = running_since.lock().unwrap();
+                                        if running.is_none() {
+                                            *running = Some(Instant::now());
+                                            break;
+                                        }
+                                    }
+                                    idle.notified().await;
+                                }
+                                tokio::task::spawn_blocking(move || {
+                                    let results = match stream_fn {
+                                        // The batches are sent as they come, none are left at
+                                        // the end
+                                        Some(stream_fn) => {
+                                            stream_matches(stream_fn, &context);
+                                            Vec::new()
+                                        }
+                                        None => score_matches(
+                                            &q,
+                                            plugin_fn(q.as_ref().into()),
+                                            actions_fn,
+                                            score_fn,
+                                        ),
+                                    };
+                                    *running_since.lock().unwrap() = None;
+                                    idle.notify_one();
+                                    results
+                                })
+                                .await
+                                .unwrap_or_default()
+                            };
+                            // Aborting doesn't stop a blocking task, so the deadline is kept
+                            // by the task waiting for it instead. It includes the time spent
+                            // waiting for an earlier call to return.
+                            pending_results.push(tokio::spawn(async move {
+                                let start = Instant::now();
+                                let results = match timeout {
+                                    Some(timeout) => tokio::time::timeout(timeout, call).await.ok(),
+                                    None => Some(call.await),
+                                };
+                                match results {
+                                    Some(results) => (QueryOutcome::Matches(results), idx),
+                                    None => (QueryOutcome::TimedOut(start.elapsed()), idx),
+                                }
+                            }));
                         }
                     }
-                    Request::Handle { plugin, selection } => {
+                    Request::Handle {
+                        plugin,
+                        selection,
+                        action,
+                    } => {
+                        // Answered even if it can't be handled, the client waits for it
+                        let result = handle_selection(state, &plugin, selection, action)
+                            .unwrap_or(HandleResult::Close);
+                        socket.send(&Response::Handled { plugin, result }).await?;
+                    }
+                    Request::HandleBatch { selections } => {
+                        let results = selections
+                            .into_iter()
+                            .filter_map(|(plugin, selection)| {
+                                handle_selection(state, &plugin, selection, None)
+                                    .map(|result| (plugin, result))
+                            })
+                            .collect();
+                        socket.send(&Response::HandledBatch { results }).await?;
+                    }
+                    Request::Preview { plugin, selection } => {
+                        abort_all(&mut pending_preview);
+
                         if let Some(&idx) = state.plugin_map.get(&plugin.name.to_string()) {
-                            let p = &state.plugins[idx];
-                            let result = p.plugin.handle_selection()(selection);
-                            socket.send(&Response::Handled { plugin, result }).await?;
+                            let preview_fn = state.plugins[idx].preview;
+                            pending_preview.push(tokio::task::spawn_blocking(move || {
+                                let preview = preview_fn.and_then(|preview_fn| {
+                                    match preview_fn(selection.clone()).into_option()? {
+                                        anyrun_plugin::Preview::Text(text) => {
+                                            Some(Preview::Text(text.into()))
+                                        }
+                                        anyrun_plugin::Preview::Markup(markup) => {
+                                            Some(Preview::Markup(markup.into()))
+                                        }
+                                        anyrun_plugin::Preview::Image(image) => {
+                                            Some(Preview::Image(image.into()))
+                                        }
+                                        anyrun_plugin::Preview::File(path) => {
+                                            Some(Preview::File(path.into()))
+                                        }
+                                    }
+                                });
+                                (idx, selection, preview)
+                            }));
                         }
                     }
+                    Request::LoadPlugin { .. } | Request::UnloadPlugin { .. } => {
+                        // The indices of the plugins change, so the work in progress is dropped
+                        abort_all(&mut pending_results);
+                        abort_all(&mut pending_preview);
+                        generation.fetch_add(1, Ordering::Relaxed);
+
+                        let response = match request {
+                            Request::LoadPlugin { path } => {
+                                let result = state.load(&path).map(|plugin| state.add(plugin));
+                                if let Err(error) = &result {
+                                    eprintln!("[anyrun-provider] {error}");
+                                }
+                                Response::PluginLoaded { result }
+                            }
+                            Request::UnloadPlugin { name } => Response::PluginUnloaded {
+                                unloaded: state.remove(&name),
+                                name,
+                            },
+                            _ => unreachable!(),
+                        };
+                        streamed = vec![None; state.plugins.len()];
+                        socket.send(&response).await?;
+                    }
+                    Request::ListPlugins => {
+                        let plugins = state
+                            .plugins
+                            .iter()
+                            .map(|p| LoadedPlugin {
+                                info: p.info.clone(),
+                                path: p.path.clone(),
+                            })
+                            .collect();
+                        socket.send(&Response::Plugins { plugins }).await?;
+                    }
                     Request::Reset => {
-                        pending_results.clear();
-                        for p in &mut state.plugins {
-                            p.plugin.init()(state.config_dir.as_ref().into());
-                        }
+                        generation.fetch_add(1, Ordering::Relaxed);
+                        abort_all(&mut pending_results);
+                        state.reset().await;
+                    }
+                    Request::Detach => {
+                        detached = true;
+                        break;
                     }
                     Request::Quit => return Ok(WorkerResult::Quit),
                 }
             }
         }
     }
+
+    // Nothing is left waiting for the work of this client
+    generation.fetch_add(1, Ordering::Relaxed);
+    abort_all(&mut pending_results);
+    abort_all(&mut pending_preview);
+    // The next client finds the plugins as if they were just started, while the client
+    // that detached doesn't have to wait for it
+    if detached {
+        state.reset().await;
+    }
     Ok(WorkerResult::Continue)
 }
 
-fn find_plugin(name: &PathBuf, dirs: &[PathBuf]) -> Option<PathBuf> {
-    if name.is_absolute() && name.exists() {
-        return Some(name.clone());
+/// Aborts the tasks and drops them. Calls into plugins that already started run on until they
+/// return, as blocking tasks can't be aborted.
+fn abort_all<T>(tasks: &mut FuturesUnordered<JoinHandle<T>>) {
+    for task in tasks.iter() {
+        task.abort();
+    }
+    tasks.clear();
+}
+
+/// Hands the selection to the plugin that provided it and records it for frecency. Returns
+/// `None` if the plugin isn't loaded or can't run the action.
+fn handle_selection(
+    state: &mut State,
+    plugin: &PluginInfo,
+    selection: Match,
+    action: Option<String>,
+) -> Option<HandleResult> {
+    let &idx = state.plugin_map.get(&plugin.name.to_string())?;
+    let p = &state.plugins[idx];
+    record_selection(state.frecency.as_mut(), plugin, &selection);
+    match (action, p.actions) {
+        (Some(action), Some((_, handle_action))) => Some(handle_action(selection, action.into())),
+        (Some(action), None) => {
+            eprintln!(
+                "[anyrun-provider] Plugin '{}' has no action handler for '{action}'",
+                plugin.name
+            );
+            None
+        }
+        (None, _) => Some(p.plugin.handle_selection()(selection)),
+    }
+}
+
+/// Remembers the selection for frecency
+fn record_selection(frecency: Option<&mut Frecency>, plugin: &PluginInfo, selection: &Match) {
+    if let Some(frecency) = frecency {
+        frecency.refresh();
+        frecency.record(&plugin.name, selection);
+        if let Err(why) = frecency.save() {
+            eprintln!("[anyrun-provider] Failed to save frecency store: {why}");
+        }
+    }
+}
+
+/// What a plugin streaming its matches is called with
+struct StreamContext {
+    idx: usize,
+    /// The generation of the query the plugin was called with
+    generation: u64,
+    /// The generation of the latest query, later ones make the batches outdated
+    current: Arc<AtomicU64>,
+    query: Arc<str>,
+    actions: Option<ActionsFn>,
+    score: Option<ScoreFn>,
+    tx: UnboundedSender<StreamedBatch>,
+}
+
+/// Passed to the plugin through the `MatchSink`
+struct SinkContext<'a> {
+    stream: &'a StreamContext,
+    tx: std_mpsc::Sender<RVec<Match>>,
+}
+
+/// Calls a plugin streaming its matches. The batches are scored on a thread of their own:
+/// the plugin may hold the lock on its data while pushing them, which its `actions` and
+/// `score` take as well.
+fn stream_matches(stream_fn: StreamMatchesFn, context: &StreamContext) {
+    let (tx, rx) = std_mpsc::channel::<RVec<Match>>();
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            for matches in rx {
+                if context.current.load(Ordering::Relaxed) != context.generation {
+                    break;
+                }
+                let results =
+                    score_matches(&context.query, matches, context.actions, context.score);
+                if context
+                    .tx
+                    .send((context.idx, context.generation, results))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        // Dropped once the plugin returns, which ends the scoring thread
+        let sink_context = SinkContext {
+            stream: context,
+            tx,
+        };
+        let sink = MatchSink::new(&sink_context as *const SinkContext as *const (), push_batch);
+        stream_fn(context.query.as_ref().into(), &sink);
+    });
+}
+
+extern "C" fn push_batch(context: *const (), matches: RVec<Match>) -> bool {
+    // SAFETY: The sink points to the context, which outlives the call to `stream_matches`
+    // the sink is passed to
+    let context = unsafe { &*(context as *const SinkContext) };
+    context.stream.current.load(Ordering::Relaxed) == context.stream.generation
+        && context.tx.send(matches).is_ok()
+}
+
+/// Scores the matches of a plugin, by the score it reports or by how well they fit the query,
+/// and looks up their secondary actions
+fn score_matches(
+    query: &str,
+    matches: RVec<Match>,
+    actions_fn: Option<ActionsFn>,
+    score_fn: Option<ScoreFn>,
+) -> Vec<RankedMatch> {
+    let reported: Vec<Option<f64>> = matches
+        .iter()
+        .map(|_match| {
+            score_fn.and_then(|score_fn| score_fn(query.into(), _match.clone()).into_option())
+        })
+        .collect();
+    let scores = relevance::score(query, &matches, &reported);
+    matches
+        .into_iter()
+        .zip(scores)
+        .map(|(_match, score)| {
+            let actions = match actions_fn {
+                Some(actions_fn) => actions_fn(_match.clone())
+                    .into_iter()
+                    .map(|action| MatchAction {
+                        id: action.id.into(),
+                        title: action.title.into(),
+                        icon: action.icon.map(Into::into).into(),
+                        keybind: action.keybind.map(Into::into).into(),
+                    })
+                    .collect(),
+                None => Vec::new(),
+            };
+            (_match, score, actions)
+        })
+        .collect()
+}
+
+/// Boosts the matches of a plugin by frecency and limits their amount
+fn rank_matches(
+    plugin: &PluginInfo,
+    mut results: Vec<RankedMatch>,
+    frecency: Option<&Frecency>,
+    limit: Option<usize>,
+) -> MatchesFields {
+    if let Some(frecency) = frecency {
+        frecency.rerank(&plugin.name, &mut results);
+    }
+    if let Some(limit) = limit {
+        results.truncate(limit);
+    }
+    let mut matches = RVec::with_capacity(results.len());
+    let mut scores = Vec::with_capacity(results.len());
+    let mut actions = Vec::with_capacity(results.len());
+    for (_match, score, match_actions) in results {
+        matches.push(_match);
+        scores.push(score);
+        actions.push(match_actions);
+    }
+    (matches, scores, actions)
+}
+
+/// Finds the plugin and loads its module, along with the library for its optional functions
+fn load_plugin(
+    plugin_path: &Path,
+    dirs: &[PathBuf],
+    libraries: &mut HashSet<PathBuf>,
+) -> Result<(PluginRef, Option<RawLibrary>), PluginError> {
+    let error = |stage, message: String| PluginError {
+        path: plugin_path.to_path_buf(),
+        stage,
+        message,
+    };
+    let path = find_plugin(plugin_path, dirs).ok_or_else(|| {
+        let dirs: Vec<_> = dirs.iter().map(|dir| dir.display().to_string()).collect();
+        error(LoadStage::Find, format!("searched {}", dirs.join(", ")))
+    })?;
+
+    // Opening a library that is already open returns the one in memory, so a rebuilt plugin is
+    // loaded from a copy instead. The copy can be removed once it is open.
+    let copy = if libraries.insert(path.clone()) {
+        None
+    } else {
+        let copy = PathBuf::from(format!(
+            "{}/anyrun-plugin-{}-{}.so",
+            env::var("XDG_RUNTIME_DIR").unwrap_or("/tmp".to_string()),
+            process::id(),
+            COPY_COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::copy(&path, &copy).map_err(|why| error(LoadStage::Open, why.to_string()))?;
+        Some(copy)
+    };
+    let lib_path = copy.as_ref().unwrap_or(&path);
+
+    let plugin = abi_stable::library::lib_header_from_path(lib_path)
+        .map_err(|why| error(LoadStage::Open, why.to_string()))
+        .and_then(|header| {
+            header
+                .init_root_module::<PluginRef>()
+                .map_err(|why| error(LoadStage::Load, why.to_string()))
+        });
+    let lib = plugin.is_ok().then(|| open_library(lib_path)).flatten();
+    if let Some(copy) = &copy {
+        let _ = fs::remove_file(copy);
+    }
+    Ok((plugin?, lib))
+}
+
+/// Opens the plugin library again to look up the optional functions it exports. The handle
+/// is kept with the plugin, and closed when it is unloaded.
+fn open_library(path: &Path) -> Option<RawLibrary> {
+    RawLibrary::load_at(path).ok()
+}
+
+/// Looks up the optional functions for secondary actions exported by the plugin.
+/// Both must be present for the actions to be usable.
+fn load_actions(lib: &RawLibrary) -> Option<(ActionsFn, HandleActionFn)> {
+    // SAFETY: The symbols are generated by the `actions` and `action_handler` macros
+    // with these exact signatures
+    unsafe {
+        let actions = *lib.get::<ActionsFn>(ACTIONS_SYMBOL).ok()?;
+        let handle_action = *lib.get::<HandleActionFn>(HANDLE_ACTION_SYMBOL).ok()?;
+        Some((actions, handle_action))
     }
-    for dir in dirs {
-        let p = dir.join(name);
-        if p.exists() {
-            return Some(p);
+}
+
+/// Looks up the optional function streaming the matches exported by the plugin
+fn load_stream(lib: &RawLibrary) -> Option<StreamMatchesFn> {
+    // SAFETY: The symbol is generated by the `stream_matches` macro with this exact signature
+    unsafe {
+        lib.get::<StreamMatchesFn>(STREAM_MATCHES_SYMBOL)
+            .ok()
+            .map(|stream| *stream)
+    }
+}
+
+/// Looks up the optional function reporting the relevance of matches exported by the plugin
+fn load_score(lib: &RawLibrary) -> Option<ScoreFn> {
+    // SAFETY: The symbol is generated by the `score` macro with this exact signature
+    unsafe { lib.get::<ScoreFn>(SCORE_SYMBOL).ok().map(|score| *score) }
+}
+
+/// Looks up the optional function for previews exported by the plugin
+fn load_preview(lib: &RawLibrary) -> Option<PreviewFn> {
+    // SAFETY: The symbol is generated by the `preview` macro with this exact signature
+    unsafe {
+        lib.get::<PreviewFn>(PREVIEW_SYMBOL)
+            .ok()
+            .map(|preview| *preview)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyrun_interface::abi_stable::std_types::{ROption, RString};
+    use std::sync::RwLock;
+
+    /// The data of a plugin, locked like the functions generated by the macros do
+    static DATA: RwLock<()> = RwLock::new(());
+
+    fn titled(title: &str) -> Match {
+        Match {
+            title: title.into(),
+            description: ROption::RNone,
+            use_pango: false,
+            icon: ROption::RNone,
+            id: ROption::RNone,
         }
+    }
 
-        let lib_name = format!("lib{}.so", name.to_string_lossy().replace('-', "_"));
-        let p = dir.join(lib_name);
-        if p.exists() {
-            return Some(p);
+    /// Like `#[stream_matches]` taking `&mut T`
+    extern "C" fn stream_with_data(input: RString, sink: &MatchSink) {
+        let _data = DATA.write().unwrap();
+        for i in 0..3 {
+            sink.push(vec![titled(&format!("{input} {i}"))].into());
         }
     }
-    None
+
+    /// Like `#[actions]` taking `&T`
+    extern "C" fn actions_with_data(_selection: Match) -> RVec<anyrun_plugin::MatchAction> {
+        let _data = DATA.read().unwrap();
+        vec![anyrun_plugin::MatchAction {
+            id: "open".into(),
+            title: "Open".into(),
+            icon: ROption::RNone,
+            keybind: ROption::RNone,
+        }]
+        .into()
+    }
+
+    #[test]
+    fn streamed_batches_are_scored_outside_of_the_plugin_call() {
+        let (batch_tx, mut batch_rx) = mpsc::unbounded_channel();
+        let context = StreamContext {
+            idx: 0,
+            generation: 1,
+            current: Arc::new(AtomicU64::new(1)),
+            query: "file".into(),
+            actions: Some(actions_with_data),
+            score: None,
+            tx: batch_tx,
+        };
+
+        let (done_tx, done_rx) = std_mpsc::channel();
+        thread::spawn(move || {
+            stream_matches(stream_with_data, &context);
+            let _ = done_tx.send(());
+        });
+        done_rx
+            .recv_timeout(Duration::from_secs(5))
+            .expect("the plugin deadlocked");
+
+        let mut titles = Vec::new();
+        while let Ok((idx, generation, results)) = batch_rx.try_recv() {
+            assert_eq!((idx, generation), (0, 1));
+            for (_match, _, actions) in results {
+                assert_eq!(actions.len(), 1);
+                titles.push(_match.title.to_string());
+            }
+        }
+        assert_eq!(titles, ["file 0", "file 1", "file 2"]);
+    }
+
+    #[test]
+    fn outdated_streams_are_stopped() {
+        let (batch_tx, mut batch_rx) = mpsc::unbounded_channel();
+        let context = StreamContext {
+            idx: 0,
+            generation: 1,
+            current: Arc::new(AtomicU64::new(2)),
+            query: "file".into(),
+            actions: None,
+            score: None,
+            tx: batch_tx,
+        };
+        stream_matches(stream_with_data, &context);
+        assert!(batch_rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn plugin_values_are_parsed() {
+        assert_eq!(
+            parse_plugin_value::<u64>("Applications=250"),
+            Ok(("Applications".to_string(), 250))
+        );
+    }
+
+    #[test]
+    fn plugin_names_can_contain_equals_signs() {
+        assert_eq!(
+            parse_plugin_value::<usize>("a=b=3"),
+            Ok(("a=b".to_string(), 3))
+        );
+    }
+
+    #[test]
+    fn invalid_plugin_values_are_rejected() {
+        assert!(parse_plugin_value::<u64>("Applications").is_err());
+        assert!(parse_plugin_value::<u64>("Applications=soon").is_err());
+        assert!(parse_plugin_value::<usize>("Applications=-1").is_err());
+    }
 }
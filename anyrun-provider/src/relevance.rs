@@ -0,0 +1,138 @@
+use anyrun_interface::{Match, abi_stable::std_types::ROption};
+
+/// How much of the final score comes from the position the plugin gave the match,
+/// the rest is decided by how well the match text fits the query.
+const RANK_WEIGHT: f64 = 0.5;
+
+/// Computes a relevance score in the range `0.0..=1.0` for each of the matches returned
+/// by a single plugin.
+///
+/// `reported` holds the scores the plugin reported itself, in the same order as `matches`.
+/// Those are used as they are. For the other matches the relevance is guessed from their
+/// order, so the position is first normalized per plugin and then combined with a query
+/// fit score that is comparable across plugins.
+pub fn score(query: &str, matches: &[Match], reported: &[Option<f64>]) -> Vec<f64> {
+    let query = query.trim().to_lowercase();
+    let len = matches.len();
+
+    matches
+        .iter()
+        .enumerate()
+        .map(|(i, _match)| {
+            if let Some(score) = reported.get(i).copied().flatten().filter(|s| !s.is_nan()) {
+                return score.clamp(0.0, 1.0);
+            }
+
+            let rank = if len > 1 {
+                1.0 - i as f64 / (len - 1) as f64
+            } else {
+                1.0
+            };
+
+            let fit = text_fit(&query, &_match.title).max(match &_match.description {
+                // Hits in the description are worth less than ones in the title
+                ROption::RSome(desc) => text_fit(&query, desc) * 0.8,
+                ROption::RNone => 0.0,
+            });
+
+            RANK_WEIGHT * rank + (1.0 - RANK_WEIGHT) * fit
+        })
+        .collect()
+}
+
+/// How well `text` fits the already lowercased `query`
+fn text_fit(query: &str, text: &str) -> f64 {
+    if query.is_empty() {
+        return 1.0;
+    }
+
+    let text = text.to_lowercase();
+
+    if text == query {
+        1.0
+    } else if text.starts_with(query) {
+        0.9
+    } else if text
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|word| word.starts_with(query))
+    {
+        0.75
+    } else if text.contains(query) {
+        0.6
+    } else if is_subsequence(query, &text) {
+        0.4
+    } else {
+        // Plugins such as calculators don't echo the query back, so they still get a base score
+        0.2
+    }
+}
+
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut haystack = haystack.chars();
+    needle.chars().all(|c| haystack.any(|h| h == c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyrun_interface::abi_stable::std_types::RString;
+
+    fn matches(titles: &[&str]) -> Vec<Match> {
+        titles
+            .iter()
+            .map(|title| Match {
+                title: RString::from(*title),
+                description: ROption::RNone,
+                use_pango: false,
+                icon: ROption::RNone,
+                id: ROption::RNone,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn earlier_matches_score_higher() {
+        let scores = score("fire", &matches(&["firefox", "firewall", "bonfire"]), &[]);
+        assert!(scores[0] > scores[1]);
+        assert!(scores[1] > scores[2]);
+    }
+
+    #[test]
+    fn fit_outweighs_a_close_position() {
+        let scores = score("term", &matches(&["xyz", "terminal", "a", "b", "c"]), &[]);
+        assert!(scores[1] > scores[0]);
+    }
+
+    #[test]
+    fn scores_are_in_range() {
+        let scores = score("", &matches(&["a", "b", "c"]), &[]);
+        assert_eq!(scores[0], 1.0);
+        assert!(scores.iter().all(|score| (0.0..=1.0).contains(score)));
+        assert_eq!(score("a", &matches(&["a"]), &[]), vec![1.0]);
+        assert!(score("a", &[], &[]).is_empty());
+    }
+
+    #[test]
+    fn reported_scores_are_used() {
+        let scores = score(
+            "q",
+            &matches(&["a", "b", "c", "d"]),
+            &[Some(0.3), None, Some(2.0), Some(f64::NAN)],
+        );
+        assert_eq!(scores[0], 0.3);
+        assert_eq!(scores[2], 1.0);
+        // Missing and invalid scores fall back to the guess
+        let guessed = score("q", &matches(&["a", "b", "c", "d"]), &[]);
+        assert_eq!(scores[1], guessed[1]);
+        assert_eq!(scores[3], guessed[3]);
+    }
+
+    #[test]
+    fn text_fit_order() {
+        assert_eq!(text_fit("fire", "fire"), 1.0);
+        assert!(text_fit("fire", "firefox") > text_fit("fire", "mozilla firefox"));
+        assert!(text_fit("fire", "mozilla firefox") > text_fit("fire", "bonfire"));
+        assert!(text_fit("fire", "bonfire") > text_fit("fire", "fix the ire"));
+        assert!(text_fit("fire", "fix the ire") > text_fit("fire", "nothing"));
+    }
+}
@@ -0,0 +1,735 @@
+use anyrun_interface::{HandleResult, Match, PluginInfo};
+use anyrun_provider_ipc::{LoadStage, LoadedPlugin, PluginError, Request, Response, Socket};
+use std::{
+    collections::{HashMap, VecDeque},
+    env,
+    ffi::OsString,
+    fs,
+    io::{self, IsTerminal, Read},
+    path::PathBuf,
+    process,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::AsyncWriteExt,
+    net::{UnixListener, UnixStream},
+    process::{Child, Command},
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+};
+
+use crate::{Args, RankedMatch, WorkerResult, frecency::Frecency, rank_matches, record_selection};
+
+/// How many times in a row a crashing host is restarted before it is given up on
+const MAX_RESTARTS: u32 = 3;
+/// A host that stayed up for this long is restarted as if it never crashed before
+const RESTART_RESET: Duration = Duration::from_secs(30);
+
+/// Tells apart the sockets of the hosts started by this process
+static SOCKET_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// A response of a host, or `None` once the connection to it is lost
+type HostEvent = (usize, Option<Response>);
+/// A host that was restarted in the background
+type Restarted = (usize, io::Result<Started>);
+
+/// Runs the plugins in child processes of their own, so a plugin crashing only takes its
+/// own host down. The hosts are `anyrun-provider` itself connecting back to the supervisor,
+/// which passes the requests of the client on to them.
+pub struct Supervisor {
+    /// The arguments every host is started with, apart from its plugins
+    host_args: Vec<OsString>,
+    /// The stdin of the provider, passed on to every host
+    stdin: Arc<[u8]>,
+    hosts: Vec<Host>,
+    events_tx: UnboundedSender<HostEvent>,
+    events_rx: UnboundedReceiver<HostEvent>,
+    restarted_tx: UnboundedSender<Restarted>,
+    restarted_rx: UnboundedReceiver<Restarted>,
+    frecency: Option<Frecency>,
+    /// Frecency has to be applied before the limit, so the hosts aren't told about it
+    max_entries: Vec<(String, usize)>,
+    /// Sent again to hosts that are restarted while the client waits for their matches
+    last_query: Option<String>,
+}
+
+struct Host {
+    plugins: Vec<PathBuf>,
+    /// The plugins as reported by the host
+    loaded: Vec<LoadedPlugin>,
+    /// The plugins the host failed to load, or all of them if it failed to start
+    errors: Vec<PluginError>,
+    /// Passes requests to the host, `None` while it isn't running
+    tx: Option<UnboundedSender<Request>>,
+    child: Option<Child>,
+    started: Instant,
+    restarts: u32,
+}
+
+/// A host that was started and is ready
+struct Started {
+    child: Child,
+    tx: UnboundedSender<Request>,
+    loaded: Vec<LoadedPlugin>,
+    errors: Vec<PluginError>,
+}
+
+/// A `Request::HandleBatch` split between the hosts, answered once all of them have
+struct PendingBatch {
+    /// The host each selection was passed to, in the order of the selections
+    order: Vec<usize>,
+    /// The hosts that haven't answered yet
+    waiting: Vec<usize>,
+    results: Vec<VecDeque<(PluginInfo, HandleResult)>>,
+}
+
+impl PendingBatch {
+    /// Takes the results of a host. Returns all of them in the order of the selections once
+    /// every host has answered.
+    fn answer(
+        &mut self,
+        host: usize,
+        results: Vec<(PluginInfo, HandleResult)>,
+    ) -> Option<Vec<(PluginInfo, HandleResult)>> {
+        self.waiting.retain(|waiting| *waiting != host);
+        self.results[host].extend(results);
+        if !self.waiting.is_empty() {
+            return None;
+        }
+        Some(
+            self.order
+                .iter()
+                .filter_map(|&host| self.results[host].pop_front())
+                .collect(),
+        )
+    }
+}
+
+impl Supervisor {
+    /// Starts a host for every plugin, or for every group of `--isolate-group`, and waits for
+    /// them to load their plugins
+    pub async fn start(args: &Args, config_dir: &str) -> Self {
+        let mut host_args: Vec<OsString> = vec![
+            "--config-dir".into(),
+            config_dir.into(),
+            "--no-frecency".into(),
+        ];
+        if let Some(ms) = args.default_timeout {
+            host_args.push("--default-timeout".into());
+            host_args.push(ms.to_string().into());
+        }
+        for (name, ms) in &args.timeout {
+            host_args.push("--timeout".into());
+            host_args.push(format!("{name}={ms}").into());
+        }
+        for name in &args.skip_empty_query {
+            host_args.push("--skip-empty-query".into());
+            host_args.push(name.into());
+        }
+
+        let mut stdin = Vec::new();
+        if !io::stdin().is_terminal() {
+            let _ = io::stdin().lock().read_to_end(&mut stdin);
+        }
+
+        let groups: Vec<Vec<PathBuf>> = args
+            .isolate_group
+            .iter()
+            .map(|group| group.split(',').map(PathBuf::from).collect())
+            .collect();
+        let mut hosts: Vec<Vec<PathBuf>> = Vec::new();
+        let mut group_hosts: HashMap<usize, usize> = HashMap::new();
+        for plugin in &args.plugins {
+            match groups.iter().position(|group| group.contains(plugin)) {
+                Some(group) => match group_hosts.get(&group) {
+                    Some(&host) => hosts[host].push(plugin.clone()),
+                    None => {
+                        group_hosts.insert(group, hosts.len());
+                        hosts.push(vec![plugin.clone()]);
+                    }
+                },
+                None => hosts.push(vec![plugin.clone()]),
+            }
+        }
+
+        let (events_tx, events_rx) = mpsc::unbounded_channel();
+        let (restarted_tx, restarted_rx) = mpsc::unbounded_channel();
+        let mut supervisor = Self {
+            host_args,
+            stdin: stdin.into(),
+            hosts: hosts.into_iter().map(Host::new).collect(),
+            events_tx,
+            events_rx,
+            restarted_tx,
+            restarted_rx,
+            frecency: (!args.no_frecency).then(|| Frecency::load(args.frecency_exclude.clone())),
+            max_entries: args.max_entries.clone(),
+            last_query: None,
+        };
+
+        let started =
+            futures::future::join_all((0..supervisor.hosts.len()).map(|i| supervisor.spawn(i)))
+                .await;
+        for (host, res) in supervisor.hosts.iter_mut().zip(started) {
+            match res {
+                Ok(started) => host.set_started(started),
+                Err(why) => host.set_failed(why),
+            }
+        }
+        supervisor
+    }
+
+    /// Starts the host at `idx` and waits for it to be ready
+    async fn spawn(&self, idx: usize) -> io::Result<Started> {
+        spawn_host(
+            idx,
+            self.args_of(idx),
+            Arc::clone(&self.stdin),
+            self.events_tx.clone(),
+        )
+        .await
+    }
+
+    /// The arguments the host at `idx` is started with
+    fn args_of(&self, idx: usize) -> Vec<OsString> {
+        let mut args = self.host_args.clone();
+        for plugin in &self.hosts[idx].plugins {
+            args.push("-p".into());
+            args.push(plugin.into());
+        }
+        args
+    }
+
+    /// Starts a host again after it crashed, unless it keeps crashing. The host is started
+    /// in the background, and taken by `restarted` once it is ready.
+    fn restart(&mut self, idx: usize) {
+        let args = self.args_of(idx);
+        let host = &mut self.hosts[idx];
+        host.tx = None;
+        let crashed = host.child.take();
+        if host.started.elapsed() > RESTART_RESET {
+            host.restarts = 0;
+        }
+        if host.restarts >= MAX_RESTARTS {
+            eprintln!(
+                "[anyrun-provider] The host of {} crashed {MAX_RESTARTS} times in a row, not restarting it",
+                host.describe()
+            );
+            return;
+        }
+        host.restarts += 1;
+
+        let stdin = Arc::clone(&self.stdin);
+        let events = self.events_tx.clone();
+        let restarted = self.restarted_tx.clone();
+        tokio::spawn(async move {
+            if let Some(mut child) = crashed {
+                let _ = child.kill().await;
+            }
+            let _ = restarted.send((idx, spawn_host(idx, args, stdin, events).await));
+        });
+    }
+
+    /// Takes a host that was restarted in the background
+    fn restarted(&mut self, idx: usize, result: io::Result<Started>) {
+        match result {
+            Ok(started) => {
+                if let Some(text) = &self.last_query {
+                    let _ = started.tx.send(Request::Query { text: text.clone() });
+                }
+                let host = &mut self.hosts[idx];
+                host.set_started(started);
+                // Plugins unloaded in the meantime are dropped again
+                for plugin in host.loaded.clone() {
+                    if !host.plugins.contains(&plugin.path) {
+                        host.unload(plugin.info.name.as_str());
+                    }
+                }
+            }
+            Err(why) => self.hosts[idx].set_failed(why),
+        }
+    }
+
+    /// Starts a host for a plugin loaded at runtime. The plugins with the same name in the
+    /// other hosts are unloaded once it is ready.
+    async fn load(&mut self, path: PathBuf) -> Result<PluginInfo, PluginError> {
+        let idx = self.hosts.len();
+        self.hosts.push(Host::new(vec![path.clone()]));
+        let started = match self.spawn(idx).await {
+            Ok(started) => started,
+            Err(why) => {
+                // Nothing was started for it, so the index can be reused
+                self.hosts.pop();
+                return Err(PluginError {
+                    path,
+                    stage: LoadStage::Load,
+                    message: format!("The plugin host failed to start: {why}"),
+                });
+            }
+        };
+
+        let host = &mut self.hosts[idx];
+        host.set_started(started);
+        if let Some(error) = host.errors.pop() {
+            host.stop();
+            return Err(error);
+        }
+        let info = host.loaded[0].info.clone();
+        while let Some(old) = self.host_of(info.name.as_str()).filter(|&old| old != idx) {
+            self.hosts[old].unload(info.name.as_str());
+        }
+        Ok(info)
+    }
+
+    /// Unloads the plugin from the host running it, returns whether there was one
+    fn unload(&mut self, name: &str) -> bool {
+        match self.host_of(name) {
+            Some(host) => {
+                self.hosts[host].unload(name);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Passes the request to the host, returns whether it is running
+    fn send(&self, host: usize, request: Request) -> bool {
+        self.hosts[host]
+            .tx
+            .as_ref()
+            .is_some_and(|tx| tx.send(request).is_ok())
+    }
+
+    fn broadcast(&self, request: &Request) {
+        for host in 0..self.hosts.len() {
+            self.send(host, request.clone());
+        }
+    }
+
+    /// The host running the plugin with the name
+    fn host_of(&self, name: &str) -> Option<usize> {
+        self.hosts.iter().position(|host| {
+            host.loaded
+                .iter()
+                .any(|plugin| plugin.info.name.as_str() == name)
+        })
+    }
+
+    async fn quit(&mut self) {
+        self.broadcast(&Request::Quit);
+        for host in &mut self.hosts {
+            host.tx = None;
+            if let Some(mut child) = host.child.take() {
+                let _ = child.wait().await;
+            }
+        }
+    }
+}
+
+impl Host {
+    fn new(plugins: Vec<PathBuf>) -> Self {
+        Self {
+            plugins,
+            loaded: Vec::new(),
+            errors: Vec::new(),
+            tx: None,
+            child: None,
+            started: Instant::now(),
+            restarts: 0,
+        }
+    }
+
+    fn set_started(&mut self, started: Started) {
+        self.child = Some(started.child);
+        self.tx = Some(started.tx);
+        self.loaded = started.loaded;
+        self.errors = started.errors;
+        self.started = Instant::now();
+    }
+
+    /// Reports all the plugins of the host as failed to load
+    fn set_failed(&mut self, why: io::Error) {
+        eprintln!(
+            "[anyrun-provider] Failed to start the host of {}: {why}",
+            self.describe()
+        );
+        self.errors = self
+            .plugins
+            .iter()
+            .map(|path| PluginError {
+                path: path.clone(),
+                stage: LoadStage::Load,
+                message: format!("The plugin host failed to start: {why}"),
+            })
+            .collect();
+    }
+
+    /// Drops the plugin from the host, stopping the host if it was the last one
+    fn unload(&mut self, name: &str) {
+        let Some(i) = self
+            .loaded
+            .iter()
+            .position(|plugin| plugin.info.name.as_str() == name)
+        else {
+            return;
+        };
+        let plugin = self.loaded.remove(i);
+        self.plugins.retain(|path| *path != plugin.path);
+        if self.loaded.is_empty() {
+            self.stop();
+        } else if let Some(tx) = &self.tx {
+            let _ = tx.send(Request::UnloadPlugin {
+                name: name.to_string(),
+            });
+        }
+    }
+
+    /// Shuts the host down for good. Hosts without a sender aren't restarted when their
+    /// connection is lost.
+    fn stop(&mut self) {
+        self.plugins.clear();
+        self.loaded.clear();
+        if let Some(tx) = self.tx.take() {
+            let _ = tx.send(Request::Quit);
+        }
+    }
+
+    fn describe(&self) -> String {
+        let plugins: Vec<_> = self
+            .plugins
+            .iter()
+            .map(|plugin| plugin.to_string_lossy())
+            .collect();
+        plugins.join(", ")
+    }
+}
+
+/// Starts a host with `args` and waits for it to be ready
+async fn spawn_host(
+    idx: usize,
+    args: Vec<OsString>,
+    stdin: Arc<[u8]>,
+    events: UnboundedSender<HostEvent>,
+) -> io::Result<Started> {
+    let socket_path = format!(
+        "{}/anyrun-host-{}-{}.sock",
+        env::var("XDG_RUNTIME_DIR").unwrap_or("/tmp".to_string()),
+        process::id(),
+        SOCKET_COUNT.fetch_add(1, Ordering::Relaxed)
+    );
+    let _ = fs::remove_file(&socket_path);
+    let listener = UnixListener::bind(&socket_path)?;
+
+    let mut child = Command::new(env::current_exe()?)
+        .args(&args)
+        .arg("connect-to")
+        .arg(&socket_path)
+        .stdin(process::Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    if let Some(mut child_stdin) = child.stdin.take() {
+        tokio::spawn(async move {
+            let _ = child_stdin.write_all(&stdin).await;
+        });
+    }
+
+    // The host only connects once its plugins are loaded and initialized
+    let stream = tokio::select! {
+        res = listener.accept() => res.map(|(stream, _)| stream),
+        _ = child.wait() => Err(io::Error::other("The host exited before connecting")),
+    };
+    let _ = fs::remove_file(&socket_path);
+    let mut socket = Socket::new(stream?);
+    let Response::Ready { errors, .. } = socket.recv().await? else {
+        return Err(io::Error::other("Expected the host to be ready"));
+    };
+    // The paths tell which plugin to drop from the host when one is unloaded
+    socket.send(&Request::ListPlugins).await?;
+    let Response::Plugins { plugins: loaded } = socket.recv().await? else {
+        return Err(io::Error::other("Expected the host to list its plugins"));
+    };
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    tokio::spawn(forward(idx, socket, rx, events));
+    Ok(Started {
+        child,
+        tx,
+        loaded,
+        errors,
+    })
+}
+
+/// Passes requests to a host and its responses back to the supervisor
+async fn forward(
+    host: usize,
+    mut socket: Socket,
+    mut rx: UnboundedReceiver<Request>,
+    events: UnboundedSender<HostEvent>,
+) {
+    loop {
+        tokio::select! {
+            req = rx.recv() => match req {
+                Some(req) => {
+                    if socket.send(&req).await.is_err() {
+                        break;
+                    }
+                }
+                // The host is being shut down on purpose
+                None => return,
+            },
+            res = socket.recv() => match res {
+                Ok(response) => {
+                    let _ = events.send((host, Some(response)));
+                }
+                Err(_) => break,
+            },
+        }
+    }
+    let _ = events.send((host, None));
+}
+
+pub async fn worker(stream: UnixStream, supervisor: &mut Supervisor) -> io::Result<WorkerResult> {
+    let mut socket = Socket::new(stream);
+
+    // Responses meant for the previous client are dropped, hosts that crashed in the
+    // meantime are restarted
+    while let Ok((host, response)) = supervisor.events_rx.try_recv() {
+        if response.is_none() && supervisor.hosts[host].tx.is_some() {
+            supervisor.restart(host);
+        }
+    }
+    while let Ok((host, result)) = supervisor.restarted_rx.try_recv() {
+        supervisor.restarted(host, result);
+    }
+
+    let info = supervisor
+        .hosts
+        .iter()
+        .flat_map(|host| host.loaded.iter().map(|plugin| plugin.info.clone()))
+        .collect();
+    let errors = supervisor
+        .hosts
+        .iter()
+        .flat_map(|host| host.errors.iter().cloned())
+        .collect();
+    socket.send(&Response::Ready { info, errors }).await?;
+
+    let mut batch: Option<PendingBatch> = None;
+    // The host and plugin of the `Request::Handle` waiting for an answer
+    let mut handling: Option<(usize, PluginInfo)> = None;
+    // Amount of matches sent for the current query per streaming plugin, as the limit applies
+    // to all of their batches together
+    let mut streamed: HashMap<String, usize> = HashMap::new();
+
+    loop {
+        tokio::select! {
+            Some((host, response)) = supervisor.events_rx.recv() => {
+                match response {
+                    Some(Response::Matches {
+                        plugin,
+                        matches,
+                        scores,
+                        actions,
+                        query,
+                        append,
+                        partial,
+                        slow,
+                    }) => {
+                        let results: Vec<RankedMatch> = matches
+                            .into_iter()
+                            .zip(scores)
+                            .zip(actions)
+                            .map(|((_match, score), actions)| (_match, score, actions))
+                            .collect();
+                        let name = plugin.name.to_string();
+                        let sent = if append {
+                            streamed.remove(&name).unwrap_or_default()
+                        } else {
+                            0
+                        };
+                        let limit = supervisor
+                            .max_entries
+                            .iter()
+                            .find(|(max_name, _)| *max_name == name)
+                            .map(|(_, max)| max.saturating_sub(sent));
+                        let (matches, scores, actions) = rank_matches(
+                            &plugin,
+                            results,
+                            supervisor.frecency.as_ref(),
+                            limit,
+                        );
+                        if partial {
+                            streamed.insert(name, sent + matches.len());
+                        }
+                        socket.send(&Response::Matches {
+                            plugin,
+                            matches,
+                            scores,
+                            actions,
+                            query,
+                            append,
+                            partial,
+                            slow,
+                        }).await?;
+                    }
+                    Some(response @ Response::Handled { .. }) => {
+                        handling = None;
+                        socket.send(&response).await?;
+                    }
+                    Some(Response::HandledBatch { results }) => {
+                        let results = batch.as_mut().and_then(|batch| batch.answer(host, results));
+                        if let Some(results) = results {
+                            batch = None;
+                            socket.send(&Response::HandledBatch { results }).await?;
+                        }
+                    }
+                    // Answers to the requests the supervisor sends on its own
+                    Some(
+                        Response::PluginLoaded { .. }
+                        | Response::PluginUnloaded { .. }
+                        | Response::Plugins { .. },
+                    ) => (),
+                    Some(response) => socket.send(&response).await?,
+                    // The host was stopped on purpose
+                    None if supervisor.hosts[host].tx.is_none() => (),
+                    None => {
+                        for plugin in supervisor.hosts[host].loaded.clone() {
+                            let plugin = plugin.info;
+                            eprintln!("[anyrun-provider] Plugin '{}' crashed", plugin.name);
+                            socket.send(&Response::PluginCrashed { plugin }).await?;
+                        }
+                        let results =
+                            batch.as_mut().and_then(|batch| batch.answer(host, Vec::new()));
+                        if let Some(results) = results {
+                            batch = None;
+                            socket.send(&Response::HandledBatch { results }).await?;
+                        }
+                        if let Some((_, plugin)) = handling.take_if(|(waiting, _)| *waiting == host) {
+                            socket.send(&Response::Handled {
+                                plugin,
+                                result: HandleResult::Close,
+                            }).await?;
+                        }
+                        supervisor.restart(host);
+                    }
+                }
+            }
+
+            Some((host, result)) = supervisor.restarted_rx.recv() => {
+                supervisor.restarted(host, result);
+            }
+
+            req_result = socket.recv() => {
+                let request = match req_result {
+                    Ok(req) => req,
+                    Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                    Err(e) => return Err(e),
+                };
+
+                match request {
+                    Request::Query { text } => {
+                        // Picks up selections and resets from other providers
+                        if let Some(frecency) = &mut supervisor.frecency {
+                            frecency.refresh();
+                        }
+                        streamed.clear();
+                        supervisor.last_query = Some(text.clone());
+                        supervisor.broadcast(&Request::Query { text });
+                    }
+                    Request::Handle { plugin, selection, action } => {
+                        match supervisor.host_of(plugin.name.as_str()) {
+                            Some(host) if supervisor.hosts[host].tx.is_some() => {
+                                record_selection(supervisor.frecency.as_mut(), &plugin, &selection);
+                                supervisor.send(host, Request::Handle {
+                                    plugin: plugin.clone(),
+                                    selection,
+                                    action,
+                                });
+                                handling = Some((host, plugin));
+                            }
+                            // The client waits for an answer
+                            _ => socket.send(&Response::Handled {
+                                plugin,
+                                result: HandleResult::Close,
+                            }).await?,
+                        }
+                    }
+                    Request::HandleBatch { selections } => {
+                        let mut order = Vec::new();
+                        let mut split: Vec<Vec<(PluginInfo, Match)>> =
+                            vec![Vec::new(); supervisor.hosts.len()];
+                        for (plugin, selection) in selections {
+                            if let Some(host) = supervisor.host_of(plugin.name.as_str()) {
+                                record_selection(supervisor.frecency.as_mut(), &plugin, &selection);
+                                order.push(host);
+                                split[host].push((plugin, selection));
+                            }
+                        }
+                        let waiting: Vec<usize> = split
+                            .into_iter()
+                            .enumerate()
+                            .filter(|(_, selections)| !selections.is_empty())
+                            .filter_map(|(host, selections)| {
+                                supervisor
+                                    .send(host, Request::HandleBatch { selections })
+                                    .then_some(host)
+                            })
+                            .collect();
+                        if waiting.is_empty() {
+                            socket.send(&Response::HandledBatch { results: Vec::new() }).await?;
+                        } else {
+                            batch = Some(PendingBatch {
+                                order,
+                                waiting,
+                                results: vec![VecDeque::new(); supervisor.hosts.len()],
+                            });
+                        }
+                    }
+                    Request::Preview { plugin, selection } => {
+                        if let Some(host) = supervisor.host_of(plugin.name.as_str()) {
+                            supervisor.send(host, Request::Preview { plugin, selection });
+                        }
+                    }
+                    Request::LoadPlugin { path } => {
+                        let result = supervisor.load(path).await;
+                        if let Ok(plugin) = &result {
+                            streamed.remove(plugin.name.as_str());
+                        }
+                        socket.send(&Response::PluginLoaded { result }).await?;
+                    }
+                    Request::UnloadPlugin { name } => {
+                        streamed.remove(&name);
+                        let unloaded = supervisor.unload(&name);
+                        socket.send(&Response::PluginUnloaded { name, unloaded }).await?;
+                    }
+                    Request::ListPlugins => {
+                        let plugins = supervisor
+                            .hosts
+                            .iter()
+                            .flat_map(|host| host.loaded.iter().cloned())
+                            .collect();
+                        socket.send(&Response::Plugins { plugins }).await?;
+                    }
+                    Request::Reset => {
+                        supervisor.last_query = None;
+                        supervisor.broadcast(&Request::Reset);
+                    }
+                    // The hosts reset their plugins on their own, while the client is gone
+                    Request::Detach => {
+                        supervisor.last_query = None;
+                        supervisor.broadcast(&Request::Reset);
+                        break;
+                    }
+                    Request::Quit => {
+                        supervisor.quit().await;
+                        return Ok(WorkerResult::Quit);
+                    }
+                }
+            }
+        }
+    }
+    Ok(WorkerResult::Continue)
+}
@@ -1,20 +1,27 @@
 use crate::{
-    config::{self, Action, Config, Keybind},
-    plugin_box::{PluginBox, PluginBoxInput, PluginBoxOutput, PluginMatch},
-    provider, Args,
+    config::{
+        self, Action, Config, ConfigArgs, Keybind, Layout, Monitor, Position, PreviewPosition,
+        ResultMode,
+    },
+    dmenu,
+    history::{History, HistorySearch},
+    preview, provider,
+    results::{PluginMatch, ResultList, Row},
+    Args,
 };
-use anyrun_interface::HandleResult;
+use anyrun_interface::{HandleResult, Match, PluginInfo};
 use anyrun_provider_ipc as ipc;
 use gtk::{gdk, gio, glib, prelude::*};
 use gtk4 as gtk;
 use gtk4_layer_shell::{Edge, LayerShell};
-use relm4::{prelude::*, ComponentBuilder, Sender};
+use relm4::{prelude::*, ComponentBuilder, Controller};
 use serde::{Deserialize, Serialize};
-use std::env;
-use std::path::PathBuf;
 use std::{
+    cmp::Reverse,
+    collections::VecDeque,
     fs,
     io::{self, Write},
+    path::PathBuf,
     sync::Arc,
 };
 use tokio::sync::mpsc;
@@ -25,6 +32,29 @@ const DEFAULT_CSS: &str = include_str!("../res/style.css");
 pub enum PostRunAction {
     Stdout(Vec<u8>),
     None,
+    /// Closed without choosing anything in dmenu mode, exiting with code 1
+    Cancelled,
+}
+
+/// Who shows Anyrun, deciding what happens once it is closed
+pub enum Caller {
+    /// Shown by this process, which exits on close
+    Local,
+    /// Shown by the daemon for a client, which gets the post run action as the reply
+    Invocation(gio::DBusMethodInvocation),
+    /// Shown by the daemon on its own, with no client waiting for a reply
+    Daemon(gio::DBusConnection),
+}
+
+impl Caller {
+    /// The connection of the daemon, used to emit its signals
+    fn connection(&self) -> Option<gio::DBusConnection> {
+        match self {
+            Caller::Local => None,
+            Caller::Invocation(invocation) => Some(invocation.connection()),
+            Caller::Daemon(conn) => Some(conn.clone()),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -39,7 +69,25 @@ pub enum AppMsg {
     },
     Action(Action),
     EntryChanged(String),
-    PluginOutput(PluginBoxOutput),
+    /// Replace the text of the entry, from the D-Bus interface
+    SetQuery(String),
+    /// A match was clicked, selecting it
+    MatchClicked(usize),
+    /// The header of the plugin at the index was clicked, collapsing or expanding it
+    HeaderClicked(usize),
+    /// Hide the plugins that failed to load until they are reported again
+    DismissLoadErrors,
+    /// Load a plugin into the provider, replacing the one with the same name. The D-Bus
+    /// call, if any, is answered with the name of the plugin.
+    LoadPlugin(PathBuf, Option<gio::DBusMethodInvocation>),
+    /// Answered with whether there was a plugin with the name
+    UnloadPlugin(String, Option<gio::DBusMethodInvocation>),
+    /// Answered with the names and paths of the plugins
+    ListPlugins(gio::DBusMethodInvocation),
+    /// A file in the config directory has changed
+    ConfigChanged,
+    /// Reload the config, the style and the plugins
+    Reload,
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -51,95 +99,531 @@ pub struct AppInit {
 
 pub struct App {
     config: Arc<Config>,
-    invocation: Option<gio::DBusMethodInvocation>,
-    plugins: FactoryVecDeque<PluginBox>,
+    config_dir: Option<String>,
+    /// Config overrides from the command line, applied again on reload
+    config_args: ConfigArgs,
+    profile: Option<String>,
+    /// Watches the config directory for changes, so they can be applied live
+    config_monitor: Option<gio::FileMonitor>,
+    reload_cancellable: Option<gio::Cancellable>,
+    caller: Caller,
+    /// Text to prefill the entry with once shown, from `anyrun show --query`
+    query: Option<String>,
+    /// The plugins in the order they are shown
+    plugins: Vec<PluginResults>,
+    results: ResultList,
     post_run_action: PostRunAction,
     tx: mpsc::Sender<anyrun_provider_ipc::Request>,
     css_provider: gtk::CssProvider,
     selected_index: usize,
     search_cancellable: Option<gio::Cancellable>,
+    history: History,
+    /// Index of the history entry currently recalled into the entry
+    history_index: Option<usize>,
+    history_search: Option<HistorySearch>,
+    /// Menu listing the secondary actions of the selected match
+    action_menu: gtk::Popover,
+    action_list: gtk::ListBox,
+    preview_visible: bool,
+    /// Set while handling a selection that should not close Anyrun
+    keep_open: bool,
+    icons_hidden: bool,
+    /// Whether the plugins that are collapsed by default have been expanded
+    collapsed_expanded: bool,
+    /// Whether the lines of stdin are offered instead of the plugins
+    dmenu: bool,
+    /// The match selected once the first matches arrive, from `--selected-row`
+    preselect: Option<usize>,
+    /// Matches to hand to their plugins together with `Action::AcceptMarked`
+    marked: Vec<PluginMatch>,
+    /// The D-Bus calls waiting for the provider to answer the plugin requests, in the order
+    /// they were sent
+    plugin_calls: VecDeque<Option<gio::DBusMethodInvocation>>,
+}
+
+/// A plugin along with its latest matches
+struct PluginResults {
+    info: PluginInfo,
+    /// The matches along with their relevance scores and secondary actions
+    matches: Vec<(Match, f64, Vec<ipc::MatchAction>)>,
+    enabled: bool,
+    /// Only the header is shown, the matches are hidden
+    collapsed: bool,
+    /// Milliseconds after which the plugin timed out on the current query
+    timed_out: Option<u64>,
+    /// The plugin has repeatedly been slow lately, so it is shown after the others
+    slow: bool,
+    /// The query the matches belong to, batches streamed for other queries are dropped
+    query: Option<String>,
 }
 
 impl App {
-    pub fn launch(
-        app: &gtk::Application,
-        app_init: AppInit,
-        invocation: Option<gio::DBusMethodInvocation>,
-    ) -> Sender<AppMsg> {
+    pub fn launch(app: &gtk::Application, app_init: AppInit, caller: Caller) -> Controller<App> {
         let builder = ComponentBuilder::<App>::default();
 
-        let connector = builder.launch((app_init, invocation));
+        let connector = builder.launch((app_init, caller));
 
-        let mut controller = connector.detach();
-        let window = controller.widget();
-        app.add_window(window);
+        let controller = connector.detach();
+        let window = controller.widget().clone();
+        app.add_window(&window);
         window.set_visible(true);
 
-        controller.detach_runtime();
-        controller.sender().clone()
+        controller
     }
 
-    fn sync_ui_selection(&self, widgets: &mut AppWidgets, matches: &[(&PluginBox, &PluginMatch)]) {
-        if matches.is_empty() {
+    fn sync_ui_selection(&self, widgets: &mut AppWidgets) {
+        if self.results.is_empty() {
             return;
         }
 
-        for plugin in self.plugins.iter() {
-            plugin
-                .matches
-                .widget()
-                .select_row(Option::<&gtk4::ListBoxRow>::None);
+        self.results.select(self.selected_index);
+        widgets._entry.grab_focus_without_selecting();
+        self.request_preview(widgets);
+    }
+
+    /// Moves the selection to the match at `index`
+    fn select_index(&mut self, widgets: &mut AppWidgets, index: usize) {
+        self.selected_index = index;
+        self.sync_ui_selection(widgets);
+    }
+
+    /// How many matches fit in the visible part of the list
+    fn page_rows(&self, widgets: &AppWidgets) -> usize {
+        let row_height = self
+            .results
+            .selected_widget()
+            .map(|widget| widget.height())
+            .filter(|height| *height > 0)
+            .unwrap_or(32);
+        ((widgets._scroll.vadjustment().page_size() / row_height as f64) as usize).max(1)
+    }
+
+    /// The index of the first match of the next or the previous plugin, wrapping around.
+    /// In `ResultMode::Mixed` a plugin's matches may appear in multiple runs, each of
+    /// which counts separately.
+    fn plugin_group_index(&self, next: bool) -> Option<usize> {
+        let plugins = (0..self.results.len())
+            .filter_map(|i| self.results.plugin_of(i))
+            .collect::<Vec<_>>();
+        let starts = (0..plugins.len())
+            .filter(|&i| i == 0 || plugins[i] != plugins[i - 1])
+            .collect::<Vec<_>>();
+        let current = starts
+            .iter()
+            .rposition(|&start| start <= self.selected_index)?;
+
+        let group = if next {
+            (current + 1) % starts.len()
+        } else {
+            (current + starts.len() - 1) % starts.len()
+        };
+        Some(starts[group])
+    }
+
+    /// Asks the provider for the preview of the selected match, if the preview pane is shown
+    fn request_preview(&self, widgets: &mut AppWidgets) {
+        if !self.preview_visible {
+            return;
         }
 
-        if let Some((plugin, plugin_match)) = matches.get(self.selected_index) {
-            let listbox = plugin.matches.widget();
-            let row = &plugin_match.row;
-            listbox.select_row(Some(row));
+        match self.selected_match() {
+            Some(plugin_match) => {
+                let _ = self.tx.blocking_send(ipc::Request::Preview {
+                    plugin: plugin_match.plugin_info,
+                    selection: plugin_match.content,
+                });
+            }
+            None => preview::show(&widgets._preview_text, &widgets._preview_picture, None),
+        }
+    }
 
-            let adj = widgets._scroll.vadjustment();
+    fn selected_match(&self) -> Option<PluginMatch> {
+        self.results.get(self.selected_index)
+    }
 
-            if let Some(bounds) = row.compute_bounds(&widgets._scroll) {
-                let y = bounds.y() as f64;
-                let row_height = bounds.height() as f64;
+    /// Rebuilds the rows of the result list from the latest matches of every plugin. The
+    /// list only updates the rows that changed. The selected match stays selected if it is
+    /// still shown.
+    fn rebuild(&mut self) {
+        let selected = self.selected_match();
+        let mut rows = Vec::new();
+        let mut remaining = self
+            .config
+            .max_entries
+            .map_or(usize::MAX, |max_entries| max_entries as usize);
 
-                let current_value = adj.value();
-                let page_size = adj.page_size();
+        match self.config.result_mode {
+            ResultMode::Grouped => {
+                // Plugins that are repeatedly slow go last, so they don't hold up the others.
+                // The indices still refer to `self.plugins`.
+                let order = (0..self.plugins.len())
+                    .filter(|&i| !self.plugins[i].slow)
+                    .chain((0..self.plugins.len()).filter(|&i| self.plugins[i].slow));
+                for i in order {
+                    let plugin = &self.plugins[i];
+                    let shown = if plugin.collapsed {
+                        Vec::new()
+                    } else {
+                        plugin
+                            .matches
+                            .iter()
+                            .take(remaining)
+                            .map(|(_match, score, actions)| PluginMatch {
+                                plugin_info: plugin.info.clone(),
+                                content: _match.clone(),
+                                score: *score,
+                                actions: actions.clone(),
+                            })
+                            .collect::<Vec<_>>()
+                    };
+                    // Plugins without matches, or whose matches don't fit, are hidden unless
+                    // they timed out
+                    if plugin.timed_out.is_none()
+                        && (plugin.matches.is_empty() || (shown.is_empty() && !plugin.collapsed))
+                    {
+                        continue;
+                    }
+                    remaining -= shown.len();
 
-                if y < 0.0 {
-                    adj.set_value(current_value + y);
-                } else if y + row_height > page_size {
-                    adj.set_value(current_value + (y + row_height - page_size));
+                    if !self.config.hide_plugin_info {
+                        rows.push(Row::Header {
+                            plugin: i,
+                            info: plugin.info.clone(),
+                            count: plugin.matches.len(),
+                            collapsed: plugin.collapsed,
+                            slow: plugin.timed_out,
+                        });
+                    }
+                    let settings = self.config.plugin_settings(&plugin.info.name);
+                    match settings.layout {
+                        Layout::List => {
+                            rows.extend(shown.into_iter().map(|plugin_match| Row::Match {
+                                plugin: i,
+                                plugin_match,
+                            }))
+                        }
+                        Layout::Grid => {
+                            let columns = settings.columns.max(1);
+                            rows.extend(shown.chunks(columns).map(|matches| Row::Tiles {
+                                plugin: i,
+                                matches: matches.to_vec(),
+                                columns,
+                            }))
+                        }
+                    }
+                }
+            }
+            // A single interleaved list, ordered by the weighted relevance of the matches.
+            // Ties keep the plugin order from the config.
+            ResultMode::Mixed => {
+                let mut ranked = Vec::new();
+                for (i, plugin) in self.plugins.iter().enumerate() {
+                    if plugin.collapsed {
+                        continue;
+                    }
+                    let weight = self.config.plugin_weight(&plugin.info.name);
+                    for (_match, score, actions) in &plugin.matches {
+                        ranked.push((i, _match, score * weight, actions));
+                    }
                 }
+                ranked.sort_by(|a, b| b.2.total_cmp(&a.2));
+                ranked.truncate(remaining);
+
+                rows.extend(
+                    ranked
+                        .into_iter()
+                        .map(|(i, _match, score, actions)| Row::Match {
+                            plugin: i,
+                            plugin_match: PluginMatch {
+                                plugin_info: self.plugins[i].info.clone(),
+                                content: _match.clone(),
+                                score,
+                                actions: actions.clone(),
+                            },
+                        }),
+                );
+            }
+        }
+
+        let mixed = self.config.result_mode == ResultMode::Mixed;
+        let list = self.results.widget();
+        if mixed {
+            list.add_css_class("mixed");
+        } else {
+            list.remove_css_class("mixed");
+        }
+        self.results
+            .set_plugin_names(mixed && !self.config.hide_plugin_info);
+        self.results.set_rows(rows);
+
+        self.selected_index = selected
+            .and_then(|selected| {
+                (0..self.results.len()).find(|&i| {
+                    self.results
+                        .get(i)
+                        .is_some_and(|plugin_match| plugin_match.same(&selected))
+                })
+            })
+            .unwrap_or(0);
+        self.results.select(self.selected_index);
+    }
+
+    /// Recalls an older or newer query from the history into the entry
+    fn history_step(&mut self, widgets: &mut AppWidgets, older: bool) {
+        let index = match (self.history_index, older) {
+            (None, true) => 0,
+            (None, false) => return,
+            (Some(i), true) => i + 1,
+            (Some(0), false) => {
+                self.history_index = None;
+                widgets._entry.set_text("");
+                return;
+            }
+            (Some(i), false) => i - 1,
+        };
+
+        if let Some(query) = self.history.get(index) {
+            self.history_index = Some(index);
+            widgets._entry.set_text(query);
+            widgets._entry.set_position(-1);
+        }
+    }
+
+    fn sync_history_search(&self, widgets: &mut AppWidgets) {
+        match &self.history_search {
+            Some(search) => {
+                let found = search.index.and_then(|i| self.history.get(i));
+                widgets._history_search.set_label(&match found {
+                    Some(query) => format!("(reverse-i-search) `{}`: {query}", search.needle),
+                    None => format!("(failed reverse-i-search) `{}`", search.needle),
+                });
+                widgets._history_search.set_visible(true);
+            }
+            None => widgets._history_search.set_visible(false),
+        }
+    }
+
+    /// Hands the selected match to its plugin, either as a plain selection or to run one of
+    /// its secondary actions
+    fn handle_selection(&mut self, widgets: &mut AppWidgets, action: Option<usize>) {
+        let Some(plugin_match) = self.selected_match() else {
+            return;
+        };
+        let action = match action {
+            Some(i) => match plugin_match.actions.get(i) {
+                Some(action) => Some(action.id.clone()),
+                None => return,
+            },
+            None => None,
+        };
+
+        self.history.push(&widgets._entry.text());
+        if let Err(why) = self.history.save() {
+            eprintln!("[anyrun] Failed to save query history: {why}");
+        }
+
+        self.emit_selected(&plugin_match);
+        let _ = self.tx.blocking_send(ipc::Request::Handle {
+            plugin: plugin_match.plugin_info,
+            selection: plugin_match.content,
+            action,
+        });
+    }
+
+    /// Announces the selection over D-Bus when shown by the daemon
+    fn emit_selected(&self, plugin_match: &PluginMatch) {
+        if let Some(conn) = self.caller.connection() {
+            crate::emit_signal(
+                &conn,
+                "Selected",
+                Some(
+                    &(
+                        plugin_match.plugin_info.name.as_str(),
+                        plugin_match.content.title.as_str(),
+                    )
+                        .to_variant(),
+                ),
+            );
+        }
+    }
+
+    /// Hands all the marked matches to their plugins in one batch
+    fn handle_marked(&mut self, widgets: &mut AppWidgets) {
+        self.history.push(&widgets._entry.text());
+        if let Err(why) = self.history.save() {
+            eprintln!("[anyrun] Failed to save query history: {why}");
+        }
+
+        for plugin_match in &self.marked {
+            self.emit_selected(plugin_match);
+        }
+        let _ = self.tx.blocking_send(ipc::Request::HandleBatch {
+            selections: self
+                .marked
+                .iter()
+                .map(|plugin_match| {
+                    (
+                        plugin_match.plugin_info.clone(),
+                        plugin_match.content.clone(),
+                    )
+                })
+                .collect(),
+        });
+    }
+
+    /// Queries the plugins again. With `exclusive`, only `plugin` is shown until a refresh
+    /// that isn't exclusive.
+    fn refresh(&mut self, widgets: &AppWidgets, plugin: &PluginInfo, exclusive: bool) {
+        let _ = self.tx.blocking_send(ipc::Request::Query {
+            text: widgets._entry.text().into(),
+        });
+        for results in &mut self.plugins {
+            // While normally true, in this case the function addresses will be consistent
+            // at runtime so it is fine for differentiating between them
+            results.enabled = !exclusive || results.info == *plugin;
+            if !results.enabled {
+                results.matches.clear();
+            }
+        }
+        self.rebuild();
+    }
+
+    /// Adds to what is printed on close, by the client when shown through the daemon
+    fn push_stdout(&mut self, output: Vec<u8>) {
+        match &mut self.post_run_action {
+            // Selections made while kept open are all output
+            PostRunAction::Stdout(bytes) => bytes.extend_from_slice(&output),
+            PostRunAction::None | PostRunAction::Cancelled => {
+                self.post_run_action = PostRunAction::Stdout(output)
             }
         }
-        widgets._entry.grab_focus_without_selecting();
     }
 
-    fn combined_matches(&self) -> Vec<(&PluginBox, &PluginMatch)> {
-        let total_matches: usize = self.plugins.iter().map(|p| p.matches.len()).sum();
-        let mut matches = Vec::with_capacity(total_matches);
+    /// Fills the action menu with the actions of the selected match and shows it next to it
+    fn show_action_menu(&self, widgets: &mut AppWidgets) {
+        let Some(plugin_match) = self
+            .selected_match()
+            .filter(|plugin_match| !plugin_match.actions.is_empty())
+        else {
+            return;
+        };
 
-        for plugin in self.plugins.iter() {
-            for plugin_match in plugin.matches.iter() {
-                matches.push((plugin, plugin_match));
+        self.action_list.remove_all();
+        for action in &plugin_match.actions {
+            let row = gtk::Box::builder()
+                .orientation(gtk::Orientation::Horizontal)
+                .spacing(10)
+                .css_classes(["action"])
+                .build();
+            if let Some(icon) = &action.icon {
+                row.append(
+                    &gtk::Image::builder()
+                        .icon_name(icon)
+                        .css_classes(["action"])
+                        .build(),
+                );
             }
+            row.append(
+                &gtk::Label::builder()
+                    .label(&action.title)
+                    .css_classes(["action", "title"])
+                    .hexpand(true)
+                    .xalign(0.0)
+                    .build(),
+            );
+            if let Some(keybind) = &action.keybind {
+                row.append(
+                    &gtk::Label::builder()
+                        .label(keybind)
+                        .css_classes(["action", "keybind"])
+                        .build(),
+                );
+            }
+            self.action_list.append(&row);
+        }
+        self.action_list
+            .select_row(self.action_list.row_at_index(0).as_ref());
+
+        if let Some(bounds) = self
+            .results
+            .selected_widget()
+            .and_then(|widget| widget.compute_bounds(&widgets._main))
+        {
+            self.action_menu.set_pointing_to(Some(&gdk::Rectangle::new(
+                bounds.x() as i32,
+                bounds.y() as i32,
+                bounds.width() as i32,
+                bounds.height() as i32,
+            )));
+        }
+        self.action_menu.popup();
+        if let Some(row) = self.action_list.row_at_index(0) {
+            row.grab_focus();
         }
-        matches
     }
 
-    // fn current_selection(&self) -> Option<(usize, &PluginBox, &PluginMatch)> {
-    //     let matches = self.combined_matches();
-    //     matches
-    //         .get(self.selected_index)
-    //         .map(|(p, m)| (self.selected_index, *p, *m))
-    // }
+    /// Places the window with `position` and the offsets. Anchoring lets the compositor keep
+    /// the window clear of exclusive zones unless they are ignored, and makes it grow away
+    /// from the anchored edge as matches come in.
+    fn place_anchored(
+        &self,
+        widgets: &AppWidgets,
+        root: &gtk::Window,
+        position: Position,
+        mon_width: u32,
+        mon_height: u32,
+    ) {
+        let width = self.config.width.to_val(mon_width);
+        let height = self.config.height.to_val(mon_height);
+        let offset_x = self.config.offset_x.to_val(mon_width);
+        let offset_y = self.config.offset_y.to_val(mon_height);
+        let (horizontal, vertical) = position.edges();
+
+        if self.config.close_on_click {
+            // The window covers the whole monitor to catch the clicks, so the main box is
+            // placed within it instead
+            for edge in [Edge::Left, Edge::Right, Edge::Top, Edge::Bottom] {
+                root.set_anchor(edge, true);
+            }
+            let align = |edge| match edge {
+                Some(Edge::Left | Edge::Top) => gtk::Align::Start,
+                Some(_) => gtk::Align::End,
+                None => gtk::Align::Center,
+            };
+            widgets._main.set_halign(align(horizontal));
+            widgets._main.set_valign(align(vertical));
+            widgets._main.set_size_request(width, height);
+            match horizontal {
+                Some(Edge::Left) => widgets._main.set_margin_start(offset_x),
+                Some(_) => widgets._main.set_margin_end(offset_x),
+                None => (),
+            }
+            match vertical {
+                Some(Edge::Top) => widgets._main.set_margin_top(offset_y),
+                Some(_) => widgets._main.set_margin_bottom(offset_y),
+                None => (),
+            }
+        } else {
+            for edge in [Edge::Left, Edge::Right, Edge::Top, Edge::Bottom] {
+                root.set_anchor(edge, Some(edge) == horizontal || Some(edge) == vertical);
+            }
+            root.set_default_size(width, height);
+            root.child().unwrap().set_size_request(width, height);
+            if let Some(edge) = horizontal {
+                root.set_margin(edge, offset_x);
+            }
+            if let Some(edge) = vertical {
+                root.set_margin(edge, offset_y);
+            }
+        }
+    }
 }
 
 #[relm4::component(pub)]
 impl Component for App {
     type Input = AppMsg;
     type Output = ();
-    type Init = (AppInit, Option<gio::DBusMethodInvocation>);
+    type Init = (AppInit, Caller);
     type CommandOutput = anyrun_provider_ipc::Response;
 
     view! {
@@ -193,36 +677,129 @@ impl Component for App {
                 set_hexpand: true,
                 set_css_classes: &["main"],
 
-                #[name = "_entry"]
-                  gtk::Text {
-                  set_hexpand: true,
-                  set_activates_default: false,
-                  connect_changed[sender] => move |entry| {
-                      sender.input(AppMsg::EntryChanged(entry.text().into()));
-                  },
-
-                    add_controller = gtk::EventControllerKey {
-                        connect_key_pressed[sender] => move |_, key, _, modifier| {
-                            sender.input(AppMsg::KeyPressed { key, modifier});
-                            match key {
-                                gdk::Key::Tab => glib::Propagation::Stop,
-                                _ => glib::Propagation::Proceed,
+                #[name = "_warning"]
+                gtk::Label {
+                    set_css_classes: &["warning"],
+                    set_halign: gtk::Align::Start,
+                    set_xalign: 0.0,
+                    set_wrap: true,
+                    set_visible: false,
+                },
+
+                #[name = "_load_errors"]
+                gtk::Box {
+                    set_css_classes: &["load-errors"],
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_visible: false,
+
+                    #[name = "_load_errors_label"]
+                    gtk::Label {
+                        set_css_classes: &["warning"],
+                        set_hexpand: true,
+                        set_halign: gtk::Align::Start,
+                        set_xalign: 0.0,
+                        set_wrap: true,
+                    },
+
+                    gtk::Button {
+                        set_css_classes: &["dismiss", "flat"],
+                        set_icon_name: "window-close-symbolic",
+                        set_tooltip_text: Some("Dismiss"),
+                        set_focusable: false,
+                        set_valign: gtk::Align::Start,
+                        connect_clicked[sender] => move |_| {
+                            sender.input(AppMsg::DismissLoadErrors);
+                        },
+                    },
+                },
+
+                #[name = "_history_search"]
+                gtk::Label {
+                    set_css_classes: &["history-search"],
+                    set_halign: gtk::Align::Start,
+                    set_visible: false,
+                },
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+
+                    gtk::Label {
+                        set_css_classes: &["prompt"],
+                        set_label: app_init.args.dmenu.prompt_label.as_deref().unwrap_or_default(),
+                        set_visible: app_init.args.dmenu.prompt_label.is_some(),
+                    },
+
+                    #[name = "_entry"]
+                      gtk::Text {
+                      set_hexpand: true,
+                      set_activates_default: false,
+                      set_placeholder_text: config.prompt.as_deref(),
+                      connect_changed[sender] => move |entry| {
+                          sender.input(AppMsg::EntryChanged(entry.text().into()));
+                      },
+
+                        add_controller = gtk::EventControllerKey {
+                            connect_key_pressed[sender] => move |_, key, _, modifier| {
+                                sender.input(AppMsg::KeyPressed { key, modifier});
+                                match key {
+                                    gdk::Key::Tab => glib::Propagation::Stop,
+                                    _ => glib::Propagation::Proceed,
+                                }
                             }
                         }
-                    }
+                    },
                 },
-                #[name = "_scroll"]
-                gtk::ScrolledWindow {
-                    set_vexpand: true,
-                    set_hexpand: true,
-                    set_policy: (gtk::PolicyType::Never, gtk::PolicyType::Automatic),
-
-                    #[local]
-                    plugins -> gtk::Box {
-                        set_orientation: gtk::Orientation::Vertical,
-                        set_can_focus: false,
-                        set_css_classes: &["matches"],
+
+                gtk::Box {
+                    set_orientation: match config.preview_position {
+                        PreviewPosition::Right => gtk::Orientation::Horizontal,
+                        PreviewPosition::Bottom => gtk::Orientation::Vertical,
+                    },
+                    set_homogeneous: true,
+
+                    #[name = "_scroll"]
+                    gtk::ScrolledWindow {
+                        set_vexpand: true,
                         set_hexpand: true,
+                        set_policy: (gtk::PolicyType::Never, gtk::PolicyType::Automatic),
+
+                        // Has to be the direct child for only the rows in view to be created
+                        #[local_ref]
+                        result_list -> gtk::ListView {},
+                    },
+
+                    #[name = "_preview"]
+                    gtk::ScrolledWindow {
+                        set_css_classes: &["preview"],
+                        set_vexpand: true,
+                        set_hexpand: true,
+                        set_visible: config.show_preview,
+                        set_policy: (gtk::PolicyType::Never, gtk::PolicyType::Automatic),
+
+                        gtk::Box {
+                            set_orientation: gtk::Orientation::Vertical,
+
+                            #[name = "_preview_picture"]
+                            gtk::Picture {
+                                set_css_classes: &["preview"],
+                                set_visible: false,
+                                set_can_shrink: true,
+                                set_content_fit: gtk::ContentFit::Contain,
+                                set_vexpand: true,
+                            },
+
+                            #[name = "_preview_text"]
+                            gtk::Label {
+                                set_css_classes: &["preview"],
+                                set_visible: false,
+                                set_wrap: true,
+                                set_wrap_mode: gtk::pango::WrapMode::WordChar,
+                                set_selectable: true,
+                                set_xalign: 0.0,
+                                set_yalign: 0.0,
+                                set_valign: gtk::Align::Start,
+                            }
+                        }
                     }
                 }
             }
@@ -230,57 +807,17 @@ impl Component for App {
     }
 
     fn init(
-        (app_init, invocation): Self::Init,
+        (app_init, caller): Self::Init,
         root: Self::Root,
         sender: relm4::ComponentSender<Self>,
     ) -> relm4::ComponentParts<Self> {
-        let user_dir = env::var("XDG_CONFIG_HOME")
-            .map(|c| format!("{c}/anyrun"))
-            .or_else(|_| env::var("HOME").map(|h| format!("{h}/.config/anyrun")))
-            .unwrap();
-        let config_dir = app_init
-            .args
-            .config_dir
-            .clone()
-            .map(Some)
-            .unwrap_or_else(|| {
-                if PathBuf::from(&user_dir).exists() {
-                    Some(user_dir.clone())
-                } else {
-                    ipc::CONFIG_DIRS
-                        .iter()
-                        .map(|path| path.to_string())
-                        .find(|path| PathBuf::from(path).exists())
-                }
-            });
+        let config_dir = config::config_dir(app_init.args.config_dir.clone());
 
         let css_provider = gtk::CssProvider::new();
+        load_style(&css_provider, config_dir.as_deref());
 
-        let mut config = if let Some(config_dir) = &config_dir {
-            match fs::read_to_string(format!("{config_dir}/style.css")) {
-                Ok(style) => {
-                    css_provider.load_from_string(&style);
-                }
-                Err(why) => {
-                    eprintln!("[anyrun] Failed to load CSS: {why}");
-                    css_provider.load_from_string(DEFAULT_CSS);
-                }
-            }
-            match fs::read(format!("{config_dir}/config.ron")) {
-                Ok(content) => ron::de::from_bytes(&content).unwrap_or_else(|why| {
-                    eprintln!("[anyrun] Failed to parse config file, using default values: {why}");
-                    Config::default()
-                }),
-                Err(why) => {
-                    eprintln!("[anyrun] Failed to read config file, using default values: {why}");
-                    Config::default()
-                }
-            }
-        } else {
-            eprintln!("[anyrun] No config found in any searched paths");
-            css_provider.load_from_string(DEFAULT_CSS);
-            Config::default()
-        };
+        let (mut config, warning) =
+            Config::load(config_dir.as_deref(), app_init.args.profile.as_deref());
 
         gtk::style_context_add_provider_for_display(
             &WidgetExt::display(&root),
@@ -289,43 +826,153 @@ impl Component for App {
         );
 
         config.merge_opt(app_init.args.config.clone());
+        let dmenu_mode = app_init.args.dmenu.dmenu;
+        if dmenu_mode {
+            dmenu::adjust_config(&mut config);
+        }
 
         let config = Arc::new(config);
 
-        let plugins = gtk::Box::builder().build();
-
-        let plugins_factory = FactoryVecDeque::<PluginBox>::builder()
-            .launch(plugins.clone())
-            .forward(sender.input_sender(), AppMsg::PluginOutput);
+        let results = ResultList::new(
+            config.hide_icons,
+            glib::clone!(
+                #[strong]
+                sender,
+                move |plugin| sender.input(AppMsg::HeaderClicked(plugin))
+            ),
+            glib::clone!(
+                #[strong]
+                sender,
+                move |index| sender.input(AppMsg::MatchClicked(index))
+            ),
+        );
+        let result_list = results.widget();
 
         let (tx, rx) = mpsc::channel(10);
 
-        sender.spawn_command(glib::clone!(
-            #[strong]
-            config,
-            #[strong]
-            config_dir,
-            #[strong(rename_to = stdin)]
-            app_init.stdin,
-            #[strong(rename_to = env)]
-            app_init.env,
-            move |sender| {
-                if let Err(why) = provider::worker(config, config_dir, rx, sender, stdin, env) {
+        if dmenu_mode {
+            sender.spawn_command(glib::clone!(
+                #[strong(rename_to = args)]
+                app_init.args.dmenu,
+                #[strong(rename_to = stdin)]
+                app_init.stdin,
+                move |sender| dmenu::worker(args, stdin, rx, sender)
+            ));
+        } else {
+            // Only the daemon keeps the provider running between shows
+            let warm = !matches!(caller, Caller::Local);
+            let init = provider::WorkerInit {
+                config: config.clone(),
+                config_dir: config_dir.clone(),
+                stdin: app_init.stdin.clone(),
+                env: app_init.env.clone(),
+                warm,
+            };
+            sender.spawn_command(move |sender| {
+                if let Err(why) = provider::worker(init, rx, sender) {
                     eprintln!("[anyrun] IPC worker returned an error: {why}");
                 }
+            });
+        }
+
+        let widgets = view_output!();
+        show_warning(&widgets._warning, warning);
+        if let Some(css_class) = &config.css_class {
+            root.add_css_class(css_class);
+        }
+
+        // Has to be set before the window is mapped, the size is then taken from the monitor
+        // in `AppMsg::Show`
+        match config.monitor.resolve(&WidgetExt::display(&root)) {
+            Some(monitor) => root.set_monitor(Some(&monitor)),
+            None if config.monitor != Monitor::Focused => eprintln!(
+                "[anyrun] Monitor {:?} not found, letting the compositor choose",
+                config.monitor
+            ),
+            None => (),
+        }
+
+        let config_monitor = config_dir.as_ref().and_then(|config_dir| {
+            let monitor = gio::File::for_path(config_dir)
+                .monitor_directory(gio::FileMonitorFlags::WATCH_MOVES, gio::Cancellable::NONE)
+                .map_err(|why| eprintln!("[anyrun] Failed to watch the config directory: {why}"))
+                .ok()?;
+            monitor.connect_changed(glib::clone!(
+                #[strong]
+                sender,
+                move |_, file, _, event| {
+                    let relevant = file
+                        .path()
+                        .and_then(|path| path.extension().map(|ext| ext == "ron" || ext == "css"))
+                        .unwrap_or(false);
+                    if relevant
+                        && matches!(
+                            event,
+                            gio::FileMonitorEvent::ChangesDoneHint
+                                | gio::FileMonitorEvent::Created
+                                | gio::FileMonitorEvent::Deleted
+                                | gio::FileMonitorEvent::MovedIn
+                                | gio::FileMonitorEvent::Renamed
+                        )
+                    {
+                        sender.input(AppMsg::ConfigChanged);
+                    }
+                }
+            ));
+            Some(monitor)
+        });
+
+        let action_list = gtk::ListBox::builder().css_classes(["actions"]).build();
+        action_list.connect_row_activated(glib::clone!(
+            #[strong]
+            sender,
+            move |_, row| {
+                sender.input(AppMsg::Action(Action::RunAction(row.index() as usize)));
+            }
+        ));
+        let action_menu = gtk::Popover::builder()
+            .child(&action_list)
+            .has_arrow(false)
+            .css_classes(["actions"])
+            .build();
+        action_menu.set_parent(&widgets._main);
+        action_menu.connect_closed(glib::clone!(
+            #[strong(rename_to = entry)]
+            widgets._entry,
+            move |_| {
+                entry.grab_focus_without_selecting();
             }
         ));
 
-        let widgets = view_output!();
         let model = Self {
-            invocation,
+            caller,
+            query: app_init.args.query(),
             config,
-            plugins: plugins_factory,
+            config_dir,
+            config_args: app_init.args.config.clone(),
+            profile: app_init.args.profile.clone(),
+            config_monitor,
+            reload_cancellable: None,
+            plugins: Vec::new(),
+            results,
             post_run_action: PostRunAction::None,
             tx,
             css_provider,
             selected_index: 0,
             search_cancellable: None,
+            history: History::load(config.history_size),
+            history_index: None,
+            history_search: None,
+            action_menu,
+            action_list,
+            preview_visible: config.show_preview,
+            keep_open: false,
+            icons_hidden: config.hide_icons,
+            collapsed_expanded: false,
+            dmenu: dmenu_mode,
+            preselect: app_init.args.dmenu.selected_row,
+            marked: Vec::new(),
+            plugin_calls: VecDeque::new(),
         };
 
         ComponentParts { model, widgets }
@@ -354,10 +1001,11 @@ impl Component for App {
                 let height = self.config.height.to_val(mon_height);
                 let y = self.config.y.to_val(mon_height) - height / 2;
 
-                root.set_anchor(Edge::Left, true);
-                root.set_anchor(Edge::Top, true);
-
-                if self.config.close_on_click {
+                if let Some(position) = self.config.position {
+                    self.place_anchored(widgets, root, position, mon_width, mon_height);
+                } else if self.config.close_on_click {
+                    root.set_anchor(Edge::Left, true);
+                    root.set_anchor(Edge::Top, true);
                     root.set_default_size(mon_width as i32, mon_height as i32);
                     widgets._main.set_halign(gtk::Align::Fill);
                     widgets._main.set_margin_start(x);
@@ -367,6 +1015,8 @@ impl Component for App {
                         ._main
                         .set_margin_bottom(mon_height as i32 - y - height);
                 } else {
+                    root.set_anchor(Edge::Left, true);
+                    root.set_anchor(Edge::Top, true);
                     root.set_default_size(width, height);
                     root.child().unwrap().set_size_request(width, height);
                     root.set_margin(Edge::Left, x);
@@ -375,6 +1025,17 @@ impl Component for App {
                 root.set_opacity(1.0); // Continuation of the Sway hack
                 widgets._entry.grab_focus_without_selecting();
 
+                if let Some(query) = self.query.take() {
+                    widgets._entry.set_text(&query);
+                    widgets._entry.set_position(-1);
+                } else if self.config.restore_last_query {
+                    if let Some(query) = self.history.get(0) {
+                        widgets._entry.set_text(query);
+                        // Typing replaces the restored query
+                        widgets._entry.select_region(0, -1);
+                    }
+                }
+
                 // If show_results_immediately is enabled, trigger initial search with empty input
                 if self.config.show_results_immediately {
                     let _ = self.tx.blocking_send(anyrun_provider_ipc::Request::Query {
@@ -383,30 +1044,71 @@ impl Component for App {
                 }
             }
             AppMsg::KeyPressed { key, modifier } => {
-                if let Some(Keybind { action, .. }) = self.config.keybinds.iter().find(|keybind| {
-                    keybind.key == key
-                        && keybind.ctrl == modifier.contains(gdk::ModifierType::CONTROL_MASK)
-                        && keybind.alt == modifier.contains(gdk::ModifierType::ALT_MASK)
-                        && keybind.shift == modifier.contains(gdk::ModifierType::SHIFT_MASK)
-                }) {
+                if let Some(Keybind { action, .. }) = self
+                    .config
+                    .keybinds
+                    .iter()
+                    .find(|keybind| keybind.matches(key, modifier))
+                {
                     sender.input(AppMsg::Action(*action));
+                } else if let Some(plugin_match) = self.selected_match() {
+                    // Plugin provided keybinds for the actions of the selected match
+                    if let Some(i) = plugin_match.actions.iter().position(|action| {
+                        action
+                            .keybind
+                            .as_deref()
+                            .and_then(|accel| Keybind::from_accel(accel, Action::RunAction(0)))
+                            .is_some_and(|keybind| keybind.matches(key, modifier))
+                    }) {
+                        sender.input(AppMsg::Action(Action::RunAction(i)));
+                    }
                 }
             }
             AppMsg::Action(action) => {
                 match action {
+                    Action::Close if self.history_search.is_some() => {
+                        self.history_search = None;
+                        self.sync_history_search(widgets);
+                    }
+                    Action::Select if self.history_search.is_some() => {
+                        if let Some(query) = self
+                            .history_search
+                            .take()
+                            .and_then(|search| search.index)
+                            .and_then(|i| self.history.get(i))
+                        {
+                            widgets._entry.set_text(query);
+                            widgets._entry.set_position(-1);
+                        }
+                        self.sync_history_search(widgets);
+                    }
                     Action::Close => {
-                        if let Some(invocation) = self.invocation.clone() {
-                            invocation.return_value(Some(
-                                &(serde_json::to_vec(&self.post_run_action).unwrap(),).to_variant(),
-                            ));
-                        } else {
-                            match &self.post_run_action {
-                                PostRunAction::Stdout(bytes) => {
-                                    io::stdout().lock().write_all(bytes).unwrap()
+                        if self.dmenu && matches!(self.post_run_action, PostRunAction::None) {
+                            self.post_run_action = PostRunAction::Cancelled;
+                        }
+                        if self.action_menu.parent().is_some() {
+                            self.action_menu.unparent();
+                        }
+                        if let Some(monitor) = &self.config_monitor {
+                            monitor.cancel();
+                        }
+                        match &self.caller {
+                            Caller::Local => {
+                                match &self.post_run_action {
+                                    PostRunAction::Stdout(bytes) => {
+                                        io::stdout().lock().write_all(bytes).unwrap()
+                                    }
+                                    PostRunAction::None | PostRunAction::Cancelled => (),
                                 }
-                                PostRunAction::None => (),
+                                root.application().unwrap().quit();
+                            }
+                            Caller::Invocation(invocation) => {
+                                invocation.clone().return_value(Some(
+                                    &(serde_json::to_vec(&self.post_run_action).unwrap(),)
+                                        .to_variant(),
+                                ));
                             }
-                            root.application().unwrap().quit();
+                            Caller::Daemon(_) => (),
                         }
                         // Unload the style so a new one can be loaded on next show
                         gtk::style_context_remove_provider_for_display(
@@ -414,18 +1116,69 @@ impl Component for App {
                             &self.css_provider,
                         );
                         root.close();
-                        // FIXME: Make sure the worker has actually correctly shut down before
-                        // exiting
+                        // The worker stops once it has passed this on. The daemon shuts down
+                        // the component of the window once it is removed, the other windows
+                        // keep running.
                         let _ = self.tx.blocking_send(ipc::Request::Quit);
-                        relm4::runtime_util::shutdown_all();
+                        if matches!(self.caller, Caller::Local) {
+                            relm4::runtime_util::shutdown_all();
+                        }
+                        // The client sets the exit code when shown through the daemon
+                        if matches!(self.caller, Caller::Local)
+                            && matches!(self.post_run_action, PostRunAction::Cancelled)
+                        {
+                            std::process::exit(1);
+                        }
+                    }
+                    Action::HistoryPrev => self.history_step(widgets, true),
+                    Action::HistoryNext => self.history_step(widgets, false),
+                    // Up and Down walk the history when the entry is empty, or a query
+                    // has already been recalled from it. Up only does so from the top of
+                    // the list and Down only without matches, they move the selection
+                    // otherwise.
+                    Action::Up
+                        if (self.history_index.is_some()
+                            || (widgets._entry.text().is_empty() && !self.history.is_empty()))
+                            && (self.results.is_empty() || self.selected_index == 0) =>
+                    {
+                        self.history_step(widgets, true)
                     }
-                    Action::Down | Action::Up => {
-                        let len = self.combined_matches().len();
+                    Action::Down if self.history_index.is_some() && self.results.is_empty() => {
+                        self.history_step(widgets, false)
+                    }
+                    Action::HistorySearch => {
+                        match &mut self.history_search {
+                            Some(search) => {
+                                let from = search.index.map_or(0, |i| i + 1);
+                                if let Some(index) = self.history.search(&search.needle, from) {
+                                    search.index = Some(index);
+                                }
+                            }
+                            None => {
+                                let needle = widgets._entry.text().to_string();
+                                let index = self.history.search(&needle, 0);
+                                self.history_search = Some(HistorySearch { needle, index });
+                            }
+                        }
+                        self.sync_history_search(widgets);
+                    }
+                    Action::Down | Action::Up | Action::Left | Action::Right => {
+                        let len = self.results.len();
                         if len == 0 {
                             return;
                         }
 
-                        if matches!(action, Action::Down) {
+                        let down = matches!(action, Action::Down | Action::Right);
+                        // Left and Right always move by one, Up and Down move by a row in grids
+                        let grid_index = match action {
+                            Action::Down | Action::Up => {
+                                self.results.grid_step(self.selected_index, down)
+                            }
+                            _ => None,
+                        };
+                        if let Some(index) = grid_index {
+                            self.selected_index = index;
+                        } else if down {
                             self.selected_index = (self.selected_index + 1) % len;
                         } else {
                             self.selected_index = if self.selected_index == 0 {
@@ -435,26 +1188,115 @@ impl Component for App {
                             };
                         }
 
-                        let matches = self.combined_matches();
-                        self.sync_ui_selection(widgets, &matches);
+                        self.sync_ui_selection(widgets);
                     }
-                    Action::Select => {
-                        let matches = self.combined_matches();
-                        if let Some((plugin, plugin_match)) = matches.get(self.selected_index) {
-                            let info = plugin.plugin_info.clone();
-                            let content = plugin_match.content.clone();
-
-                            drop(matches);
+                    Action::PageUp | Action::PageDown | Action::First | Action::Last => {
+                        let len = self.results.len();
+                        if len == 0 {
+                            return;
+                        }
 
-                            let _ = self.tx.blocking_send(ipc::Request::Handle {
-                                plugin: info,
-                                selection: content,
-                            });
+                        let index = match action {
+                            Action::PageUp => {
+                                self.selected_index.saturating_sub(self.page_rows(widgets))
+                            }
+                            Action::PageDown => {
+                                (self.selected_index + self.page_rows(widgets)).min(len - 1)
+                            }
+                            Action::First => 0,
+                            _ => len - 1,
+                        };
+                        self.select_index(widgets, index);
+                    }
+                    Action::NextPlugin | Action::PrevPlugin => {
+                        if let Some(index) =
+                            self.plugin_group_index(matches!(action, Action::NextPlugin))
+                        {
+                            self.select_index(widgets, index);
                         }
                     }
+                    Action::ClearEntry => {
+                        self.history_search = None;
+                        self.sync_history_search(widgets);
+                        self.history_index = None;
+                        widgets._entry.set_text("");
+                    }
+                    Action::CopySelectionTitle => {
+                        if let Some(plugin_match) = self.selected_match() {
+                            let title = &plugin_match.content.title;
+                            let text = if plugin_match.content.use_pango {
+                                gtk::pango::parse_markup(title, '\0')
+                                    .map(|(_, text, _)| text.to_string())
+                                    .unwrap_or_else(|_| title.to_string())
+                            } else {
+                                title.to_string()
+                            };
+                            root.clipboard().set_text(&text);
+                        }
+                    }
+                    Action::Select => self.handle_selection(widgets, None),
+                    Action::SelectAndKeepOpen => {
+                        self.keep_open = true;
+                        self.handle_selection(widgets, None);
+                    }
+                    Action::ToggleIcons => {
+                        self.icons_hidden = !self.icons_hidden;
+                        self.results.set_icons_hidden(self.icons_hidden);
+                    }
+                    Action::ToggleCollapsed => {
+                        self.collapsed_expanded = !self.collapsed_expanded;
+                        for plugin in &mut self.plugins {
+                            if self.config.plugin_settings(&plugin.info.name).collapsed {
+                                plugin.collapsed = !self.collapsed_expanded;
+                            }
+                        }
+                        self.rebuild();
+                        self.request_preview(widgets);
+                    }
+                    Action::ToggleMark => {
+                        if let Some(plugin_match) = self.selected_match() {
+                            match self
+                                .marked
+                                .iter()
+                                .position(|marked| marked.same(&plugin_match))
+                            {
+                                Some(i) => {
+                                    self.marked.remove(i);
+                                }
+                                None => self.marked.push(plugin_match),
+                            }
+                            self.results.set_marked(&self.marked);
+                        }
+                    }
+                    Action::AcceptMarked if self.marked.is_empty() => {
+                        self.handle_selection(widgets, None)
+                    }
+                    Action::AcceptMarked => self.handle_marked(widgets),
+                    Action::TogglePreview => {
+                        self.preview_visible = !self.preview_visible;
+                        widgets._preview.set_visible(self.preview_visible);
+                        self.request_preview(widgets);
+                    }
+                    Action::ActionMenu => self.show_action_menu(widgets),
+                    Action::RunAction(i) => {
+                        self.action_menu.popdown();
+                        self.handle_selection(widgets, Some(i));
+                    }
                 }
             }
             AppMsg::EntryChanged(text) => {
+                if let Some(search) = &mut self.history_search {
+                    search.index = self.history.search(&text, 0);
+                    search.needle = text;
+                    self.sync_history_search(widgets);
+                    return;
+                }
+
+                // Manually editing a recalled query stops walking the history
+                if self.history_index.and_then(|i| self.history.get(i)) != Some(text.as_str()) {
+                    self.history_index = None;
+                }
+
                 if let Some(cancellable) = self.search_cancellable.take() {
                     cancellable.cancel();
                 }
@@ -472,29 +1314,80 @@ impl Component for App {
                     }
                 });
             }
-            AppMsg::PluginOutput(PluginBoxOutput::MatchesLoaded) => {
-                let matches = self.combined_matches();
-                if let Some((plugin, plugin_match)) = self.combined_matches().first() {
-                    plugin.matches.widget().select_row(Some(&plugin_match.row));
+            AppMsg::SetQuery(text) => {
+                self.history_search = None;
+                self.sync_history_search(widgets);
+                widgets._entry.set_text(&text);
+                widgets._entry.set_position(-1);
+            }
+            AppMsg::ConfigChanged => {
+                // Editors often write a file in multiple steps, so wait for them to settle
+                if let Some(cancellable) = self.reload_cancellable.take() {
+                    cancellable.cancel();
                 }
 
-                if let Some(max_entries) = self.config.max_entries {
-                    for (_plugin, plugin_match) in matches.iter().skip(max_entries as usize) {
-                        plugin_match.row.set_visible(false);
+                let cancellable = gio::Cancellable::new();
+                self.reload_cancellable = Some(cancellable.clone());
+
+                let sender = sender.clone();
+                glib::MainContext::default().spawn_local(async move {
+                    glib::timeout_future(std::time::Duration::from_millis(200)).await;
+
+                    if !cancellable.is_cancelled() {
+                        sender.input(AppMsg::Reload);
                     }
-                    self.plugins.broadcast(PluginBoxInput::MaybeHide);
+                });
+            }
+            AppMsg::Reload => {
+                load_style(&self.css_provider, self.config_dir.as_deref());
+
+                let (mut config, warning) =
+                    Config::load(self.config_dir.as_deref(), self.profile.as_deref());
+                config.merge_opt(self.config_args.clone());
+                if self.dmenu {
+                    dmenu::adjust_config(&mut config);
+                }
+                show_warning(&widgets._warning, warning);
+                widgets
+                    ._entry
+                    .set_placeholder_text(config.prompt.as_deref());
+                if let Some(css_class) = &self.config.css_class {
+                    root.remove_css_class(css_class);
+                }
+                if let Some(css_class) = &config.css_class {
+                    root.add_css_class(css_class);
                 }
+                // Keybinds and the behaviour of the list apply right away, changes to the
+                // window itself, the plugin list or the provider apply when next shown
+                self.config = Arc::new(config);
+
+                // Plugins read their own configs on init
+                let _ = self.tx.blocking_send(ipc::Request::Reset);
+                let _ = self.tx.blocking_send(ipc::Request::Query {
+                    text: widgets._entry.text().into(),
+                });
             }
-            AppMsg::PluginOutput(PluginBoxOutput::RowSelected(index)) => {
-                for (i, plugin) in self.plugins.iter().enumerate() {
-                    if i != index.current_index() {
-                        plugin
-                            .matches
-                            .widget()
-                            .select_row(Option::<&gtk::ListBoxRow>::None);
-                    }
+            AppMsg::MatchClicked(index) => self.select_index(widgets, index),
+            AppMsg::HeaderClicked(plugin) => {
+                if let Some(plugin) = self.plugins.get_mut(plugin) {
+                    plugin.collapsed = !plugin.collapsed;
+                    self.rebuild();
+                    self.request_preview(widgets);
                 }
             }
+            AppMsg::DismissLoadErrors => widgets._load_errors.set_visible(false),
+            AppMsg::LoadPlugin(path, invocation) => {
+                self.plugin_calls.push_back(invocation);
+                let _ = self.tx.blocking_send(ipc::Request::LoadPlugin { path });
+            }
+            AppMsg::UnloadPlugin(name, invocation) => {
+                self.plugin_calls.push_back(invocation);
+                let _ = self.tx.blocking_send(ipc::Request::UnloadPlugin { name });
+            }
+            AppMsg::ListPlugins(invocation) => {
+                self.plugin_calls.push_back(Some(invocation));
+                let _ = self.tx.blocking_send(ipc::Request::ListPlugins);
+            }
         }
         self.update_view(widgets, sender);
     }
@@ -507,75 +1400,286 @@ impl Component for App {
         root: &Self::Root,
     ) {
         match message {
-            ipc::Response::Ready { info } => {
-                let mut guard = self.plugins.guard();
-                for info in info {
-                    guard.push_back((info, self.config.clone()));
+            ipc::Response::Ready { mut info, errors } => {
+                // The provider prints them itself as well
+                let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+                widgets._load_errors_label.set_label(&errors.join("\n"));
+                widgets._load_errors.set_visible(!errors.is_empty());
+
+                // Stable, so plugins with the same priority keep the configured order
+                info.sort_by_key(|info| Reverse(self.config.plugin_settings(&info.name).priority));
+                self.plugins = info
+                    .into_iter()
+                    .map(|info| PluginResults {
+                        collapsed: self.config.plugin_settings(&info.name).collapsed,
+                        info,
+                        matches: Vec::new(),
+                        enabled: true,
+                        timed_out: None,
+                        slow: false,
+                        query: None,
+                    })
+                    .collect();
+                self.rebuild();
+            }
+            ipc::Response::Matches {
+                plugin,
+                matches,
+                scores,
+                mut actions,
+                query,
+                append,
+                slow,
+                ..
+            } => {
+                let Some(results) = self
+                    .plugins
+                    .iter_mut()
+                    .find(|results| results.info == plugin)
+                else {
+                    return;
+                };
+
+                let query_changed = !append && results.query != query;
+                if append {
+                    if results.query != query {
+                        return;
+                    }
+                } else {
+                    results.matches.clear();
+                    results.timed_out = None;
+                    results.slow = slow;
+                    results.query = query;
+                }
+                if results.enabled {
+                    for (j, _match) in matches.into_iter().enumerate() {
+                        results.matches.push((
+                            _match,
+                            scores.get(j).copied().unwrap_or_default(),
+                            actions.get_mut(j).map(std::mem::take).unwrap_or_default(),
+                        ));
+                    }
+                }
+                self.rebuild();
+                // Matches of a new query start from the first one again
+                if query_changed {
+                    self.selected_index = 0;
+                    self.results.select(0);
+                }
+                if let Some(index) = self.preselect.take() {
+                    if index < self.results.len() {
+                        self.selected_index = index;
+                        self.results.select(index);
+                    }
+                }
+                self.request_preview(widgets);
+            }
+            ipc::Response::PluginTimedOut { plugin, elapsed_ms } => {
+                if let Some(results) = self
+                    .plugins
+                    .iter_mut()
+                    .find(|results| results.info == plugin)
+                {
+                    results.timed_out = Some(elapsed_ms);
+                    self.rebuild();
+                }
+            }
+            ipc::Response::PluginCrashed { plugin } => {
+                if let Some(results) = self
+                    .plugins
+                    .iter_mut()
+                    .find(|results| results.info == plugin)
+                {
+                    results.matches.clear();
+                    self.rebuild();
                 }
+                show_warning(
+                    &widgets._warning,
+                    Some(format!("Plugin '{}' crashed", plugin.name)),
+                );
             }
-            ipc::Response::Matches { plugin, matches } => {
-                let i = self
+            ipc::Response::PluginLoaded { result: Ok(info) } => {
+                if let Some(invocation) = self.plugin_calls.pop_front().flatten() {
+                    invocation.return_value(Some(&(info.name.to_string(),).to_variant()));
+                }
+                self.plugins
+                    .retain(|results| results.info.name != info.name);
+                let priority = self.config.plugin_settings(&info.name).priority;
+                let index = self
                     .plugins
                     .iter()
-                    .enumerate()
-                    .find_map(|(i, plugin_box)| {
-                        if plugin_box.plugin_info == plugin {
-                            Some(i)
-                        } else {
-                            None
-                        }
+                    .position(|results| {
+                        self.config.plugin_settings(&results.info.name).priority < priority
                     })
-                    .unwrap();
-
-                self.plugins.send(i, PluginBoxInput::Matches(matches));
+                    .unwrap_or(self.plugins.len());
+                self.plugins.insert(
+                    index,
+                    PluginResults {
+                        collapsed: self.config.plugin_settings(&info.name).collapsed,
+                        info,
+                        matches: Vec::new(),
+                        enabled: true,
+                        timed_out: None,
+                        slow: false,
+                        query: None,
+                    },
+                );
+                self.rebuild();
+                // The queries in progress were dropped by the provider
+                let _ = self.tx.blocking_send(ipc::Request::Query {
+                    text: widgets._entry.text().into(),
+                });
+            }
+            ipc::Response::PluginLoaded { result: Err(error) } => {
+                if let Some(invocation) = self.plugin_calls.pop_front().flatten() {
+                    invocation.return_error(gio::DBusError::Failed, &error.to_string());
+                }
+                widgets._load_errors_label.set_label(&error.to_string());
+                widgets._load_errors.set_visible(true);
+                let _ = self.tx.blocking_send(ipc::Request::Query {
+                    text: widgets._entry.text().into(),
+                });
+            }
+            ipc::Response::PluginUnloaded { name, unloaded } => {
+                if let Some(invocation) = self.plugin_calls.pop_front().flatten() {
+                    invocation.return_value(Some(&(unloaded,).to_variant()));
+                }
+                if unloaded {
+                    self.plugins
+                        .retain(|results| results.info.name.as_str() != name);
+                    self.rebuild();
+                    self.request_preview(widgets);
+                }
+                let _ = self.tx.blocking_send(ipc::Request::Query {
+                    text: widgets._entry.text().into(),
+                });
+            }
+            ipc::Response::Plugins { plugins } => {
+                if let Some(invocation) = self.plugin_calls.pop_front().flatten() {
+                    let plugins: Vec<(String, String)> = plugins
+                        .into_iter()
+                        .map(|plugin| {
+                            (
+                                plugin.info.name.to_string(),
+                                plugin.path.to_string_lossy().into_owned(),
+                            )
+                        })
+                        .collect();
+                    invocation.return_value(Some(&(plugins,).to_variant()));
+                }
             }
             ipc::Response::Handled { plugin, result } => {
+                let close = !std::mem::take(&mut self.keep_open);
                 match result {
-                    HandleResult::Close => sender.input(AppMsg::Action(Action::Close)),
-                    HandleResult::Refresh(exclusive) => {
-                        let _ = self.tx.blocking_send(ipc::Request::Query {
-                            text: widgets._entry.text().into(),
-                        });
-                        if exclusive {
-                            for (i, plugin_box) in self.plugins.iter().enumerate() {
-                                // While normally true, in this case the function addresses will be consistent
-                                // at runtime so it is fine for differentiating between them
-                                if plugin_box.plugin_info != plugin {
-                                    self.plugins.send(i, PluginBoxInput::Enable(false));
-                                }
-                            }
-                        } else {
-                            self.plugins.broadcast(PluginBoxInput::Enable(true));
+                    HandleResult::Close => {
+                        if close {
+                            sender.input(AppMsg::Action(Action::Close));
                         }
                     }
+                    HandleResult::Refresh(exclusive) => self.refresh(widgets, &plugin, exclusive),
                     HandleResult::Copy(rvec) => {
-                        let vec = rvec.to_vec();
-                        let mime = tree_magic_mini::from_u8(&rvec);
-                        if match mime {
-                            "TEXT" | "STRING" | "UTF8_STRING" => true,
-                            mime if mime.starts_with("text/") => true,
-                            _ => false,
-                        } {
-                            root.clipboard().set_text(&String::from_utf8_lossy(&rvec));
-                        } else {
-                            let content = gdk::ContentProvider::for_bytes(
-                                mime,
-                                &glib::Bytes::from_owned(vec.clone()),
-                            );
-                            if let Err(why) = root.clipboard().set_content(Some(&content)) {
-                                eprintln!("[anyrun] Error setting clipboard content: {why}");
-                            }
+                        set_clipboard(root, rvec.into());
+                        if close {
+                            sender.input(AppMsg::Action(Action::Close));
                         }
-                        sender.input(AppMsg::Action(Action::Close));
                     }
                     HandleResult::Stdout(rvec) => {
-                        io::stdout().lock().write_all(&rvec).unwrap();
-                        self.post_run_action = PostRunAction::Stdout(rvec.into());
-                        sender.input(AppMsg::Action(Action::Close));
+                        self.push_stdout(rvec.into());
+                        if close {
+                            sender.input(AppMsg::Action(Action::Close));
+                        }
                     }
                 }
             }
+            ipc::Response::HandledBatch { results } => {
+                let close = !std::mem::take(&mut self.keep_open);
+                let mut refresh = false;
+                let mut copied = Vec::new();
+                for (plugin, result) in results {
+                    match result {
+                        HandleResult::Close => (),
+                        HandleResult::Refresh(exclusive) => {
+                            refresh = true;
+                            self.refresh(widgets, &plugin, exclusive);
+                        }
+                        // Copied and printed output of each selection goes on its own line
+                        HandleResult::Copy(rvec) => {
+                            if !copied.is_empty() {
+                                copied.push(b'\n');
+                            }
+                            copied.extend_from_slice(&rvec);
+                        }
+                        HandleResult::Stdout(rvec) => {
+                            let mut output = rvec.to_vec();
+                            if !output.ends_with(b"\n") {
+                                output.push(b'\n');
+                            }
+                            self.push_stdout(output);
+                        }
+                    }
+                }
+                if !copied.is_empty() {
+                    set_clipboard(root, copied);
+                }
+
+                self.marked.clear();
+                self.results.set_marked(&self.marked);
+                if close && !refresh {
+                    sender.input(AppMsg::Action(Action::Close));
+                }
+            }
+            ipc::Response::Preview {
+                plugin,
+                selection,
+                preview,
+            } => {
+                // The selection may have already moved on while the preview was prepared
+                if self.selected_match().is_some_and(|plugin_match| {
+                    plugin_match.plugin_info == plugin
+                        && same_match(&plugin_match.content, &selection)
+                }) {
+                    preview::show(&widgets._preview_text, &widgets._preview_picture, preview);
+                }
+            }
         }
         self.update_view(widgets, sender);
     }
 }
+
+/// Loads `style.css` from the config directory, or the default style if it can't be read
+fn load_style(css_provider: &gtk::CssProvider, config_dir: Option<&str>) {
+    match config_dir.map(|config_dir| fs::read_to_string(format!("{config_dir}/style.css"))) {
+        Some(Ok(style)) => css_provider.load_from_string(&style),
+        Some(Err(why)) => {
+            eprintln!("[anyrun] Failed to load CSS: {why}");
+            css_provider.load_from_string(DEFAULT_CSS);
+        }
+        None => css_provider.load_from_string(DEFAULT_CSS),
+    }
+}
+
+/// Copies text as such, anything else with its detected mime type
+fn set_clipboard(root: &gtk::Window, bytes: Vec<u8>) {
+    let mime = tree_magic_mini::from_u8(&bytes);
+    if match mime {
+        "TEXT" | "STRING" | "UTF8_STRING" => true,
+        mime if mime.starts_with("text/") => true,
+        _ => false,
+    } {
+        root.clipboard().set_text(&String::from_utf8_lossy(&bytes));
+    } else {
+        let content = gdk::ContentProvider::for_bytes(mime, &glib::Bytes::from_owned(bytes));
+        if let Err(why) = root.clipboard().set_content(Some(&content)) {
+            eprintln!("[anyrun] Error setting clipboard content: {why}");
+        }
+    }
+}
+
+fn show_warning(label: &gtk::Label, warning: Option<String>) {
+    label.set_label(warning.as_deref().unwrap_or_default());
+    label.set_visible(warning.is_some());
+}
+
+fn same_match(a: &Match, b: &Match) -> bool {
+    a.title == b.title && a.description == b.description && a.id == b.id
+}
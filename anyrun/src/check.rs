@@ -0,0 +1,180 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use anyrun_provider_ipc as ipc;
+use gtk4::gdk;
+use serde::Deserialize;
+
+use crate::{
+    config::{self, Config},
+    Args,
+};
+
+/// A lenient view of `config.ron`, used to check the keybinds and plugins one by one even
+/// when the config as a whole does not parse
+#[derive(Deserialize)]
+struct RawConfig {
+    #[serde(default)]
+    plugins: Option<Vec<PathBuf>>,
+    #[serde(default)]
+    keybinds: Option<Vec<RawKeybind>>,
+}
+
+#[derive(Deserialize)]
+struct RawKeybind {
+    #[serde(default)]
+    ctrl: bool,
+    #[serde(default)]
+    alt: bool,
+    #[serde(default)]
+    shift: bool,
+    key: String,
+}
+
+impl RawKeybind {
+    fn describe(&self) -> String {
+        let mut keybind = String::new();
+        for (enabled, modifier) in [
+            (self.ctrl, "Ctrl+"),
+            (self.alt, "Alt+"),
+            (self.shift, "Shift+"),
+        ] {
+            if enabled {
+                keybind.push_str(modifier);
+            }
+        }
+        keybind.push_str(&self.key);
+        keybind
+    }
+}
+
+/// Checks the config directory, printing every problem found. Returns whether everything
+/// is fine.
+pub fn check_config(args: &Args) -> bool {
+    let Some(config_dir) = config::config_dir(args.config_dir.clone()) else {
+        eprintln!("No config directory found in any searched paths");
+        return false;
+    };
+    let config_dir = PathBuf::from(config_dir);
+    println!("Checking {}", config_dir.display());
+
+    let mut errors = Vec::new();
+
+    let config_path = config_dir.join("config.ron");
+    let (config, raw) = match fs::read_to_string(&config_path) {
+        Ok(content) => {
+            let config = match ron::from_str::<Config>(&content) {
+                Ok(config) => Some(config),
+                Err(why) => {
+                    errors.push(format!("{}:{why}", config_path.display()));
+                    None
+                }
+            };
+            (config, ron::from_str::<RawConfig>(&content).ok())
+        }
+        Err(why) if why.kind() == io::ErrorKind::NotFound => {
+            println!("{} not found, using default values", config_path.display());
+            (Some(Config::default()), None)
+        }
+        Err(why) => {
+            errors.push(format!("{}: {why}", config_path.display()));
+            (None, None)
+        }
+    };
+
+    // Plugins define their own config formats, so only the syntax can be checked
+    let mut plugin_configs = fs::read_dir(&config_dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| {
+                    path.extension().is_some_and(|ext| ext == "ron") && *path != config_path
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    plugin_configs.sort();
+    for path in plugin_configs {
+        match fs::read_to_string(&path) {
+            Ok(content) => {
+                if let Err(why) = ron::from_str::<ron::Value>(&content) {
+                    errors.push(format!("{}:{why}", path.display()));
+                }
+            }
+            Err(why) => errors.push(format!("{}: {why}", path.display())),
+        }
+    }
+
+    let plugins = match config {
+        Some(mut config) => {
+            if let Some(profile) = &args.profile {
+                match config.profiles.remove(profile) {
+                    Some(overrides) => config.apply_profile(overrides),
+                    None => errors.push(format!("Profile `{profile}` does not exist")),
+                }
+            }
+            config.merge_opt(args.config.clone());
+            config.plugins
+        }
+        None => raw
+            .as_ref()
+            .and_then(|raw| raw.plugins.clone())
+            .unwrap_or_default(),
+    };
+    let plugin_dirs = ipc::plugin_dirs(Path::new(&config::user_dir()));
+    for plugin in &plugins {
+        if ipc::find_plugin(plugin, &plugin_dirs).is_none() {
+            errors.push(format!(
+                "Plugin `{}` not found in any of: {}",
+                plugin.display(),
+                plugin_dirs
+                    .iter()
+                    .map(|dir| dir.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+    }
+
+    let keybinds = raw.and_then(|raw| raw.keybinds).unwrap_or_default();
+    let mut seen = HashMap::new();
+    for (i, keybind) in keybinds.iter().enumerate() {
+        if gdk::Key::from_name(&keybind.key).is_none() {
+            errors.push(format!(
+                "Keybind {}: `{}` is not a valid GDK key name",
+                i + 1,
+                keybind.key
+            ));
+            continue;
+        }
+
+        match seen.entry((
+            keybind.ctrl,
+            keybind.alt,
+            keybind.shift,
+            keybind.key.as_str(),
+        )) {
+            Entry::Occupied(first) => errors.push(format!(
+                "Keybinds {} and {} both use {}, only the first one is used",
+                first.get() + 1,
+                i + 1,
+                keybind.describe()
+            )),
+            Entry::Vacant(entry) => {
+                entry.insert(i);
+            }
+        }
+    }
+
+    for error in &errors {
+        println!("{error}");
+    }
+    if errors.is_empty() {
+        println!("No problems found");
+    }
+
+    errors.is_empty()
+}
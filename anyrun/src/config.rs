@@ -1,9 +1,36 @@
 use anyrun_macros::ConfigArgs;
 use clap::ValueEnum;
-use gtk::gdk;
+use gtk::{gdk, prelude::*};
 use gtk4 as gtk;
+use gtk4_layer_shell::Edge;
 use serde::{de::Visitor, Deserialize, Deserializer, Serialize};
-use std::path::PathBuf;
+use std::{collections::HashMap, env, fs, io, path::PathBuf, str::FromStr};
+
+use anyrun_provider_ipc as ipc;
+
+/// The config directory of the user, which plugins are also loaded from
+pub fn user_dir() -> String {
+    env::var("XDG_CONFIG_HOME")
+        .map(|c| format!("{c}/anyrun"))
+        .or_else(|_| env::var("HOME").map(|h| format!("{h}/.config/anyrun")))
+        .unwrap()
+}
+
+/// Resolves the config directory to use, either the given one, the user's or the first
+/// existing system wide one
+pub fn config_dir(config_dir: Option<String>) -> Option<String> {
+    config_dir.map(Some).unwrap_or_else(|| {
+        let user_dir = user_dir();
+        if PathBuf::from(&user_dir).exists() {
+            Some(user_dir)
+        } else {
+            ipc::CONFIG_DIRS
+                .iter()
+                .map(|path| path.to_string())
+                .find(|path| PathBuf::from(path).exists())
+        }
+    })
+}
 
 #[derive(Deserialize, ConfigArgs)]
 #[config_args(pub)]
@@ -41,13 +68,192 @@ pub struct Config {
     pub layer: Layer,
     #[serde(default = "Config::default_keyboard_mode")]
     pub keyboard_mode: KeyboardMode,
+    #[serde(default = "Config::default_result_mode")]
+    pub result_mode: ResultMode,
+    /// The monitor the window is shown on
+    #[serde(default = "Config::default_monitor")]
+    pub monitor: Monitor,
+    /// Anchors the window to an edge, corner or the center of the monitor instead of placing
+    /// it with `x` and `y`
+    #[serde(default)]
+    pub position: Option<Position>,
+    /// Distance from the anchored vertical edge when `position` is set
+    #[serde(default = "Config::default_offset")]
+    pub offset_x: RelativeNum,
+    /// Distance from the anchored horizontal edge when `position` is set
+    #[serde(default = "Config::default_offset")]
+    pub offset_y: RelativeNum,
+
+    /// Multipliers applied to the relevance of a plugin's matches in `ResultMode::Mixed`,
+    /// keyed by the plugin name
+    #[config_args(skip)]
+    #[serde(default)]
+    pub plugin_weights: HashMap<String, f64>,
+
+    /// Milliseconds a plugin has to answer a query before it is marked as slow, 0 waits
+    /// indefinitely
+    #[serde(default = "Config::default_plugin_timeout")]
+    pub plugin_timeout: u64,
+
+    /// Run every plugin in a process of its own, so a crashing plugin doesn't take the
+    /// others down with it
+    #[serde(default)]
+    pub isolate_plugins: bool,
+    /// Plugins sharing a process when `isolate_plugins` is set, as they are listed in
+    /// `plugins`
+    #[config_args(skip)]
+    #[serde(default)]
+    pub plugin_groups: Vec<Vec<PathBuf>>,
+
+    /// Settings applied to the matches of a plugin, keyed by the plugin name
+    #[config_args(skip)]
+    #[serde(default)]
+    pub plugin_settings: HashMap<String, PluginSettings>,
+
+    /// Boost matches based on how often and how recently they have been selected
+    #[serde(default = "Config::default_frecency")]
+    pub frecency: bool,
+    /// Names of plugins whose matches are not affected by frecency
+    #[config_args(skip)]
+    #[serde(default)]
+    pub frecency_exclude: Vec<String>,
+
+    /// Amount of past queries to remember, 0 disables the history
+    #[serde(default = "Config::default_history_size")]
+    pub history_size: usize,
+    /// Fill the entry with the last query when shown
+    #[serde(default)]
+    pub restore_last_query: bool,
+
+    /// Show the preview pane for the selected match when opened
+    #[serde(default)]
+    pub show_preview: bool,
+    #[serde(default = "Config::default_preview_position")]
+    pub preview_position: PreviewPosition,
+
+    /// Placeholder text shown in the empty entry
+    #[serde(default)]
+    pub prompt: Option<String>,
+    /// Extra style class added to the window
+    #[serde(default)]
+    pub css_class: Option<String>,
 
     #[config_args(skip)]
     #[serde(default = "Config::default_keybinds")]
     pub keybinds: Vec<Keybind>,
+
+    /// Named sets of overrides, selected with `--profile`
+    #[config_args(skip)]
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
 }
 
 impl Config {
+    /// Reads `config.ron` from the config directory and applies the profile, if any. If
+    /// either can't be used, the default values are used instead along with a warning
+    /// describing why.
+    pub fn load(config_dir: Option<&str>, profile: Option<&str>) -> (Self, Option<String>) {
+        let (mut config, warning) = Self::read(config_dir);
+        let Some(profile) = profile else {
+            return (config, warning);
+        };
+
+        match config.profiles.remove(profile) {
+            Some(overrides) => {
+                config.apply_profile(overrides);
+                (config, warning)
+            }
+            None => {
+                eprintln!("[anyrun] Profile `{profile}` does not exist, using the base config");
+                (
+                    config,
+                    warning.or_else(|| {
+                        Some(format!(
+                            "Profile `{profile}` does not exist, using the base config"
+                        ))
+                    }),
+                )
+            }
+        }
+    }
+
+    fn read(config_dir: Option<&str>) -> (Self, Option<String>) {
+        let Some(config_dir) = config_dir else {
+            eprintln!("[anyrun] No config found in any searched paths");
+            return (Self::default(), None);
+        };
+
+        match fs::read(format!("{config_dir}/config.ron")) {
+            Ok(content) => match ron::de::from_bytes(&content) {
+                Ok(config) => (config, None),
+                Err(why) => {
+                    eprintln!("[anyrun] Failed to parse config file, using default values: {why}");
+                    (
+                        Self::default(),
+                        Some(format!(
+                            "Failed to parse config.ron, using default values: {why}"
+                        )),
+                    )
+                }
+            },
+            // Running without a config file is fine, the defaults are meant to be usable
+            Err(why) if why.kind() == io::ErrorKind::NotFound => {
+                eprintln!("[anyrun] Failed to read config file, using default values: {why}");
+                (Self::default(), None)
+            }
+            Err(why) => {
+                eprintln!("[anyrun] Failed to read config file, using default values: {why}");
+                (
+                    Self::default(),
+                    Some(format!(
+                        "Failed to read config.ron, using default values: {why}"
+                    )),
+                )
+            }
+        }
+    }
+
+    /// Overrides the values set by the profile
+    pub fn apply_profile(&mut self, profile: Profile) {
+        macro_rules! apply {
+            ($($field:ident),*) => {
+                $(
+                    if let Some(value) = profile.$field {
+                        self.$field = value;
+                    }
+                )*
+            };
+        }
+        apply!(
+            plugins,
+            x,
+            y,
+            width,
+            height,
+            max_height,
+            monitor,
+            offset_x,
+            offset_y,
+            result_mode,
+            preview_position,
+            keybinds
+        );
+
+        self.position = profile.position.or(self.position);
+        self.prompt = profile.prompt.or(self.prompt.take());
+        self.css_class = profile.css_class.or(self.css_class.take());
+    }
+
+    /// The settings of a plugin, or the defaults if none are configured
+    pub fn plugin_settings(&self, name: &str) -> PluginSettings {
+        self.plugin_settings.get(name).cloned().unwrap_or_default()
+    }
+
+    /// The weight of a plugin's matches in `ResultMode::Mixed`
+    pub fn plugin_weight(&self, name: &str) -> f64 {
+        self.plugin_weights.get(name).copied().unwrap_or(1.0)
+    }
+
     fn default_x() -> RelativeNum {
         RelativeNum::Fraction(0.5)
     }
@@ -89,6 +295,34 @@ impl Config {
         KeyboardMode::Exclusive
     }
 
+    fn default_result_mode() -> ResultMode {
+        ResultMode::Grouped
+    }
+
+    fn default_offset() -> RelativeNum {
+        RelativeNum::Absolute(0)
+    }
+
+    fn default_monitor() -> Monitor {
+        Monitor::Focused
+    }
+
+    fn default_frecency() -> bool {
+        true
+    }
+
+    fn default_plugin_timeout() -> u64 {
+        3000
+    }
+
+    fn default_history_size() -> usize {
+        100
+    }
+
+    fn default_preview_position() -> PreviewPosition {
+        PreviewPosition::Right
+    }
+
     fn default_keybinds() -> Vec<Keybind> {
         vec![
             Keybind {
@@ -119,6 +353,20 @@ impl Config {
                 key: gdk::Key::Down,
                 action: Action::Down,
             },
+            Keybind {
+                ctrl: false,
+                alt: true,
+                shift: false,
+                key: gdk::Key::Left,
+                action: Action::Left,
+            },
+            Keybind {
+                ctrl: false,
+                alt: true,
+                shift: false,
+                key: gdk::Key::Right,
+                action: Action::Right,
+            },
             Keybind {
                 ctrl: false,
                 alt: false,
@@ -133,6 +381,118 @@ impl Config {
                 key: gdk::Key::Tab,
                 action: Action::Down,
             },
+            Keybind {
+                ctrl: true,
+                alt: false,
+                shift: false,
+                key: gdk::Key::r,
+                action: Action::HistorySearch,
+            },
+            Keybind {
+                ctrl: false,
+                alt: true,
+                shift: false,
+                key: gdk::Key::Return,
+                action: Action::ActionMenu,
+            },
+            Keybind {
+                ctrl: false,
+                alt: true,
+                shift: false,
+                key: gdk::Key::p,
+                action: Action::TogglePreview,
+            },
+            Keybind {
+                ctrl: false,
+                alt: false,
+                shift: false,
+                key: gdk::Key::Page_Up,
+                action: Action::PageUp,
+            },
+            Keybind {
+                ctrl: false,
+                alt: false,
+                shift: false,
+                key: gdk::Key::Page_Down,
+                action: Action::PageDown,
+            },
+            Keybind {
+                ctrl: true,
+                alt: false,
+                shift: false,
+                key: gdk::Key::Home,
+                action: Action::First,
+            },
+            Keybind {
+                ctrl: true,
+                alt: false,
+                shift: false,
+                key: gdk::Key::End,
+                action: Action::Last,
+            },
+            Keybind {
+                ctrl: true,
+                alt: false,
+                shift: false,
+                key: gdk::Key::Page_Down,
+                action: Action::NextPlugin,
+            },
+            Keybind {
+                ctrl: true,
+                alt: false,
+                shift: false,
+                key: gdk::Key::Page_Up,
+                action: Action::PrevPlugin,
+            },
+            Keybind {
+                ctrl: true,
+                alt: false,
+                shift: false,
+                key: gdk::Key::u,
+                action: Action::ClearEntry,
+            },
+            Keybind {
+                ctrl: false,
+                alt: true,
+                shift: false,
+                key: gdk::Key::c,
+                action: Action::CopySelectionTitle,
+            },
+            Keybind {
+                ctrl: false,
+                alt: false,
+                shift: true,
+                key: gdk::Key::Return,
+                action: Action::SelectAndKeepOpen,
+            },
+            Keybind {
+                ctrl: false,
+                alt: true,
+                shift: false,
+                key: gdk::Key::i,
+                action: Action::ToggleIcons,
+            },
+            Keybind {
+                ctrl: false,
+                alt: true,
+                shift: false,
+                key: gdk::Key::e,
+                action: Action::ToggleCollapsed,
+            },
+            Keybind {
+                ctrl: true,
+                alt: false,
+                shift: false,
+                key: gdk::Key::space,
+                action: Action::ToggleMark,
+            },
+            Keybind {
+                ctrl: true,
+                alt: false,
+                shift: false,
+                key: gdk::Key::Return,
+                action: Action::AcceptMarked,
+            },
         ]
     }
 }
@@ -154,11 +514,66 @@ impl Default for Config {
             max_entries: None,
             layer: Self::default_layer(),
             keyboard_mode: Self::default_keyboard_mode(),
+            result_mode: Self::default_result_mode(),
+            monitor: Self::default_monitor(),
+            position: None,
+            offset_x: Self::default_offset(),
+            offset_y: Self::default_offset(),
+            plugin_weights: HashMap::new(),
+            plugin_timeout: Self::default_plugin_timeout(),
+            isolate_plugins: false,
+            plugin_groups: Vec::new(),
+            plugin_settings: HashMap::new(),
+            frecency: Self::default_frecency(),
+            frecency_exclude: Vec::new(),
+            history_size: Self::default_history_size(),
+            restore_last_query: false,
+            show_preview: false,
+            preview_position: Self::default_preview_position(),
+            prompt: None,
+            css_class: None,
             keybinds: Self::default_keybinds(),
+            profiles: HashMap::new(),
         }
     }
 }
 
+/// Overrides applied on top of the base config when the profile is selected. Fields that are
+/// left out keep their value from the base config.
+#[derive(Deserialize)]
+pub struct Profile {
+    #[serde(default)]
+    pub plugins: Option<Vec<PathBuf>>,
+    #[serde(default)]
+    pub x: Option<RelativeNum>,
+    #[serde(default)]
+    pub y: Option<RelativeNum>,
+    #[serde(default)]
+    pub width: Option<RelativeNum>,
+    #[serde(default)]
+    pub height: Option<RelativeNum>,
+    #[serde(default)]
+    pub max_height: Option<RelativeNum>,
+    #[serde(default)]
+    pub monitor: Option<Monitor>,
+    #[serde(default)]
+    pub position: Option<Position>,
+    #[serde(default)]
+    pub offset_x: Option<RelativeNum>,
+    #[serde(default)]
+    pub offset_y: Option<RelativeNum>,
+    #[serde(default)]
+    pub result_mode: Option<ResultMode>,
+    #[serde(default)]
+    pub preview_position: Option<PreviewPosition>,
+    #[serde(default)]
+    pub keybinds: Option<Vec<Keybind>>,
+    #[serde(default)]
+    pub prompt: Option<String>,
+    #[serde(default)]
+    pub css_class: Option<String>,
+}
+
 #[derive(Deserialize, Serialize, Clone, Debug, ValueEnum)]
 pub enum Layer {
     Background,
@@ -173,6 +588,81 @@ pub enum KeyboardMode {
     OnDemand,
 }
 
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ResultMode {
+    /// Matches are grouped by plugin, in the order the plugins are listed
+    Grouped,
+    /// Matches from all plugins are interleaved in a single list by relevance
+    Mixed,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PreviewPosition {
+    /// Next to the matches
+    Right,
+    /// Below the matches
+    Bottom,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct PluginSettings {
+    /// Maximum amount of matches shown from the plugin
+    #[serde(default)]
+    pub max_entries: Option<usize>,
+    /// Plugins with a higher priority are shown first, regardless of the order they are
+    /// loaded in. Plugins with the same priority keep their order.
+    #[serde(default)]
+    pub priority: i32,
+    /// Only show the plugin header until expanded
+    #[serde(default)]
+    pub collapsed: bool,
+    /// Whether the plugin is queried when the entry is empty
+    #[serde(default = "PluginSettings::default_empty_query")]
+    pub empty_query: bool,
+    /// Overrides `plugin_timeout` for the plugin
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    /// How the matches are laid out, only used with `ResultMode::Grouped`
+    #[serde(default)]
+    pub layout: Layout,
+    /// Amount of tiles in each row with `Layout::Grid`
+    #[serde(default = "PluginSettings::default_columns")]
+    pub columns: usize,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Layout {
+    /// One match per row
+    #[default]
+    List,
+    /// Tiles with a large icon and the title, for launcher-style plugins
+    Grid,
+}
+
+impl PluginSettings {
+    fn default_empty_query() -> bool {
+        true
+    }
+
+    fn default_columns() -> usize {
+        6
+    }
+}
+
+impl Default for PluginSettings {
+    fn default() -> Self {
+        Self {
+            max_entries: None,
+            priority: 0,
+            collapsed: false,
+            empty_query: Self::default_empty_query(),
+            timeout: None,
+            layout: Layout::List,
+            columns: Self::default_columns(),
+        }
+    }
+}
+
 // Could have a better name
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub enum RelativeNum {
@@ -201,12 +691,105 @@ impl From<&str> for RelativeNum {
     }
 }
 
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub enum Monitor {
+    /// Leave the choice to the compositor, which usually picks the focused monitor
+    Focused,
+    /// The first monitor reported by the compositor
+    Primary,
+    /// The monitor with the given connector name, like `DP-1`
+    ByName(String),
+    /// The monitor at the given position in the list reported by the compositor
+    ByIndex(u32),
+}
+
+impl Monitor {
+    /// Finds the monitor to show the window on, `None` leaves the choice to the compositor
+    pub fn resolve(&self, display: &gdk::Display) -> Option<gdk::Monitor> {
+        let monitors = display.monitors();
+        let nth = |i| monitors.item(i).and_downcast::<gdk::Monitor>();
+        match self {
+            Monitor::Focused => None,
+            Monitor::Primary => nth(0),
+            Monitor::ByName(name) => (0..monitors.n_items())
+                .filter_map(nth)
+                .find(|monitor| monitor.connector().is_some_and(|c| c.as_str() == name)),
+            Monitor::ByIndex(index) => nth(*index),
+        }
+    }
+}
+
+/// Parsed by the value parser clap derives for `--monitor`, so an invalid index is reported
+/// as a usage error
+impl FromStr for Monitor {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Ok(match value.split_once(':') {
+            Some(("name", name)) => Self::ByName(name.to_string()),
+            Some(("index", index)) => Self::ByIndex(
+                index
+                    .parse()
+                    .map_err(|why| format!("invalid monitor index '{index}': {why}"))?,
+            ),
+            _ => match value {
+                "focused" => Self::Focused,
+                "primary" => Self::Primary,
+                _ => Self::ByName(value.to_string()),
+            },
+        })
+    }
+}
+
 #[derive(Deserialize, Debug, Clone, Copy)]
 pub enum Action {
     Close,
     Select,
     Up,
     Down,
+    /// Select the previous match, moves within a row in grids
+    Left,
+    /// Select the next match, moves within a row in grids
+    Right,
+    /// Recall the previous query from the history
+    HistoryPrev,
+    /// Recall the next query from the history
+    HistoryNext,
+    /// Incrementally search the history for the entered text
+    HistorySearch,
+    /// Open the menu of secondary actions for the selected match
+    ActionMenu,
+    /// Run the secondary action of the selected match at the given index
+    RunAction(usize),
+    /// Show or hide the preview pane
+    TogglePreview,
+    /// Move the selection up by a page
+    PageUp,
+    /// Move the selection down by a page
+    PageDown,
+    /// Select the first match
+    First,
+    /// Select the last match
+    Last,
+    /// Select the first match of the next plugin
+    NextPlugin,
+    /// Select the first match of the previous plugin
+    PrevPlugin,
+    /// Clear the text entry
+    ClearEntry,
+    /// Copy the title of the selected match to the clipboard
+    CopySelectionTitle,
+    /// Select the match without closing Anyrun afterwards
+    SelectAndKeepOpen,
+    /// Show or hide the icons
+    ToggleIcons,
+    /// Expand or collapse the plugins that are collapsed by default
+    ToggleCollapsed,
+    /// Mark or unmark the selected match
+    ToggleMark,
+    /// Hand all the marked matches to their plugins at once, or the selected one if none
+    /// are marked
+    AcceptMarked,
 }
 
 #[derive(Deserialize, Clone)]
@@ -223,6 +806,38 @@ pub struct Keybind {
 }
 
 impl Keybind {
+    /// Whether a key press with the given modifiers triggers this keybind. Letters match
+    /// regardless of case, as GDK reports them in upper case while Shift is held.
+    pub fn matches(&self, key: gdk::Key, modifier: gdk::ModifierType) -> bool {
+        self.key.to_lower() == key.to_lower()
+            && self.ctrl == modifier.contains(gdk::ModifierType::CONTROL_MASK)
+            && self.alt == modifier.contains(gdk::ModifierType::ALT_MASK)
+            && self.shift == modifier.contains(gdk::ModifierType::SHIFT_MASK)
+    }
+
+    /// Parses a keybind in the `Ctrl+Alt+Shift+<key>` format plugins use for their actions
+    pub fn from_accel(accel: &str, action: Action) -> Option<Self> {
+        let (modifiers, key) = accel.rsplit_once('+').unwrap_or(("", accel));
+        let mut keybind = Self {
+            ctrl: false,
+            alt: false,
+            shift: false,
+            key: gdk::Key::from_name(key)?,
+            action,
+        };
+
+        for modifier in modifiers.split('+').filter(|modifier| !modifier.is_empty()) {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => keybind.ctrl = true,
+                "alt" => keybind.alt = true,
+                "shift" => keybind.shift = true,
+                _ => return None,
+            }
+        }
+
+        Some(keybind)
+    }
+
     fn deserialize_key<'de, D>(deserializer: D) -> Result<gdk::Key, D::Error>
     where
         D: Deserializer<'de>,
@@ -240,7 +855,7 @@ impl Keybind {
             where
                 E: serde::de::Error,
             {
-                gdk::Key::from_name(v).ok_or(E::custom("Key name is not valid"))
+                gdk::Key::from_name(v).ok_or(E::custom(format!("Key name `{v}` is not valid")))
             }
         }
 
@@ -248,8 +863,83 @@ impl Keybind {
     }
 }
 
-#[derive(Deserialize, Clone, ValueEnum)]
-enum Position {
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Position {
     Top,
     Center,
+    Bottom,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Position {
+    /// The horizontal and vertical edges the window is anchored to, `None` centers it on
+    /// that axis
+    pub fn edges(self) -> (Option<Edge>, Option<Edge>) {
+        match self {
+            Position::Top => (None, Some(Edge::Top)),
+            Position::Center => (None, None),
+            Position::Bottom => (None, Some(Edge::Bottom)),
+            Position::TopLeft => (Some(Edge::Left), Some(Edge::Top)),
+            Position::TopRight => (Some(Edge::Right), Some(Edge::Top)),
+            Position::BottomLeft => (Some(Edge::Left), Some(Edge::Bottom)),
+            Position::BottomRight => (Some(Edge::Right), Some(Edge::Bottom)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accel_with_modifiers() {
+        let keybind = Keybind::from_accel("Ctrl+Shift+d", Action::RunAction(1)).unwrap();
+        assert!(keybind.ctrl && keybind.shift && !keybind.alt);
+        assert_eq!(keybind.key, gdk::Key::d);
+        assert!(matches!(keybind.action, Action::RunAction(1)));
+    }
+
+    #[test]
+    fn accel_modifiers_ignore_case() {
+        let keybind = Keybind::from_accel("control+ALT+Return", Action::Select).unwrap();
+        assert!(keybind.ctrl && keybind.alt && !keybind.shift);
+        assert_eq!(keybind.key, gdk::Key::Return);
+    }
+
+    #[test]
+    fn accel_without_modifiers() {
+        let keybind = Keybind::from_accel("Delete", Action::Select).unwrap();
+        assert!(!keybind.ctrl && !keybind.alt && !keybind.shift);
+        assert_eq!(keybind.key, gdk::Key::Delete);
+    }
+
+    #[test]
+    fn shifted_letters_match() {
+        let keybind = Keybind::from_accel("Ctrl+Shift+c", Action::Select).unwrap();
+        let modifier = gdk::ModifierType::CONTROL_MASK | gdk::ModifierType::SHIFT_MASK;
+        assert!(keybind.matches(gdk::Key::C, modifier));
+        assert!(keybind.matches(gdk::Key::c, modifier));
+        assert!(!keybind.matches(gdk::Key::C, gdk::ModifierType::SHIFT_MASK));
+        assert!(!keybind.matches(gdk::Key::d, modifier));
+    }
+
+    #[test]
+    fn invalid_accels() {
+        assert!(Keybind::from_accel("Super+d", Action::Select).is_none());
+        assert!(Keybind::from_accel("Ctrl+NotAKey", Action::Select).is_none());
+        assert!(Keybind::from_accel("Ctrl+", Action::Select).is_none());
+        assert!(Keybind::from_accel("", Action::Select).is_none());
+    }
+
+    #[test]
+    fn monitors() {
+        assert_eq!("focused".parse(), Ok(Monitor::Focused));
+        assert_eq!("index:1".parse(), Ok(Monitor::ByIndex(1)));
+        assert_eq!("name:DP-1".parse(), Ok(Monitor::ByName("DP-1".into())));
+        assert_eq!("HDMI-A-1".parse(), Ok(Monitor::ByName("HDMI-A-1".into())));
+        assert!("index:x".parse::<Monitor>().is_err());
+    }
 }
@@ -0,0 +1,164 @@
+use abi_stable::std_types::{ROption, RVec};
+use anyrun_interface::{HandleResult, Match, PluginInfo};
+use anyrun_provider_ipc as ipc;
+use clap::Args;
+use relm4::Sender;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Receiver;
+
+use crate::config::Config;
+
+/// The options of dmenu mode, named after their dmenu and rofi counterparts
+#[derive(Args, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct DmenuArgs {
+    /// Choose one of the lines of stdin and print it, like dmenu
+    #[arg(long)]
+    pub dmenu: bool,
+    /// Label shown before the entry
+    #[arg(short = 'p', long, requires = "dmenu")]
+    pub prompt_label: Option<String>,
+    /// Match the lines case-insensitively
+    #[arg(short = 'i', long, requires = "dmenu")]
+    pub insensitive: bool,
+    /// Print the index of the chosen line instead of the line, or -1 for typed text
+    #[arg(long, requires = "dmenu")]
+    pub print_index: bool,
+    /// Index of the line that is selected at first
+    #[arg(long, requires = "dmenu")]
+    pub selected_row: Option<usize>,
+    /// Only allow choosing one of the lines, instead of also accepting the typed text
+    #[arg(long, requires = "dmenu")]
+    pub only_match: bool,
+}
+
+pub fn plugin_info() -> PluginInfo {
+    PluginInfo {
+        name: "dmenu".into(),
+        icon: "format-indent-more".into(),
+    }
+}
+
+/// Dmenu mode shows all the lines right away, without a plugin header above them
+pub fn adjust_config(config: &mut Config) {
+    config.show_results_immediately = true;
+    config.hide_plugin_info = true;
+}
+
+/// Answers the requests of the app in place of the provider, offering the lines of stdin as
+/// the only matches
+pub fn worker(
+    args: DmenuArgs,
+    stdin: Vec<u8>,
+    mut rx: Receiver<ipc::Request>,
+    sender: Sender<ipc::Response>,
+) {
+    let lines = String::from_utf8_lossy(&stdin)
+        .lines()
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    let info = plugin_info();
+
+    sender.emit(ipc::Response::Ready {
+        info: vec![info.clone()],
+        errors: Vec::new(),
+    });
+
+    while let Some(request) = rx.blocking_recv() {
+        match request {
+            ipc::Request::Query { text } => {
+                let matches = matches(&args, &lines, &text);
+                sender.emit(ipc::Response::Matches {
+                    plugin: info.clone(),
+                    scores: vec![1.0; matches.len()],
+                    actions: vec![Vec::new(); matches.len()],
+                    matches,
+                    query: Some(text),
+                    append: false,
+                    partial: false,
+                    slow: false,
+                });
+            }
+            ipc::Request::Handle { selection, .. } => sender.emit(ipc::Response::Handled {
+                plugin: info.clone(),
+                result: output(&args, selection),
+            }),
+            ipc::Request::HandleBatch { selections } => sender.emit(ipc::Response::HandledBatch {
+                results: selections
+                    .into_iter()
+                    .map(|(plugin, selection)| (plugin, output(&args, selection)))
+                    .collect(),
+            }),
+            ipc::Request::Preview { selection, .. } => sender.emit(ipc::Response::Preview {
+                plugin: info.clone(),
+                selection,
+                preview: None,
+            }),
+            // The lines are the only plugin, there is nothing to load
+            ipc::Request::LoadPlugin { path } => sender.emit(ipc::Response::PluginLoaded {
+                result: Err(ipc::PluginError {
+                    path,
+                    stage: ipc::LoadStage::Load,
+                    message: "plugins can't be loaded in dmenu mode".to_string(),
+                }),
+            }),
+            ipc::Request::UnloadPlugin { name } => sender.emit(ipc::Response::PluginUnloaded {
+                name,
+                unloaded: false,
+            }),
+            ipc::Request::ListPlugins => sender.emit(ipc::Response::Plugins {
+                plugins: Vec::new(),
+            }),
+            ipc::Request::Reset => (),
+            ipc::Request::Detach | ipc::Request::Quit => break,
+        }
+    }
+}
+
+/// The lines containing every word of `text`, in their original order. Their index is used as
+/// the match id, the typed text has none.
+fn matches(args: &DmenuArgs, lines: &[String], text: &str) -> RVec<Match> {
+    let fold = |s: &str| {
+        if args.insensitive {
+            s.to_lowercase()
+        } else {
+            s.to_string()
+        }
+    };
+    let folded = fold(text);
+    let words = folded.split_whitespace().collect::<Vec<_>>();
+
+    let mut matches = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| {
+            let line = fold(line);
+            words.iter().all(|word| line.contains(word))
+        })
+        .map(|(i, line)| line_match(line, ROption::RSome(i as u64)))
+        .collect::<RVec<_>>();
+
+    if matches.is_empty() && !args.only_match && !text.trim().is_empty() {
+        matches.push(line_match(text, ROption::RNone));
+    }
+    matches
+}
+
+/// Prints the chosen line, or its index with `--print-index`
+fn output(args: &DmenuArgs, selection: Match) -> HandleResult {
+    let output = match selection.id {
+        ROption::RSome(index) if args.print_index => index.to_string(),
+        ROption::RNone if args.print_index => "-1".to_string(),
+        _ => selection.title.to_string(),
+    };
+    HandleResult::Stdout(format!("{output}\n").into_bytes().into())
+}
+
+fn line_match(line: &str, id: ROption<u64>) -> Match {
+    Match {
+        title: line.into(),
+        description: ROption::RNone,
+        use_pango: false,
+        icon: ROption::RNone,
+        id,
+    }
+}
@@ -0,0 +1,157 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Persisted list of queries that led to a selection, oldest first
+pub struct History {
+    entries: Vec<String>,
+    size: usize,
+    path: PathBuf,
+}
+
+/// State of an ongoing reverse search through the history
+pub struct HistorySearch {
+    /// The text being searched for
+    pub needle: String,
+    /// Index of the currently found entry, see `History::get`
+    pub index: Option<usize>,
+}
+
+impl History {
+    /// Loads the history from the state directory, keeping at most `size` entries
+    pub fn load(size: usize) -> Self {
+        let path = state_dir().join("history");
+
+        let mut entries: Vec<String> = fs::read_to_string(&path)
+            .map(|content| content.lines().map(str::to_string).collect())
+            .unwrap_or_default();
+        entries.drain(..entries.len().saturating_sub(size));
+
+        Self {
+            entries,
+            size,
+            path,
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        if self.size == 0 {
+            return Ok(());
+        }
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut content = self.entries.join("\n");
+        content.push('\n');
+        fs::write(&self.path, content)
+    }
+
+    /// Remembers a query, moving it to the front if it is already present
+    pub fn push(&mut self, query: &str) {
+        let query = query.trim();
+        if self.size == 0 || query.is_empty() {
+            return;
+        }
+
+        self.entries.retain(|entry| entry != query);
+        self.entries.push(query.to_string());
+        self.entries
+            .drain(..self.entries.len().saturating_sub(self.size));
+    }
+
+    /// Gets an entry by its age, 0 being the most recent one
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries
+            .len()
+            .checked_sub(index + 1)
+            .map(|i| self.entries[i].as_str())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Finds the most recent entry containing `needle`, starting from the entry at `from`
+    pub fn search(&self, needle: &str, from: usize) -> Option<usize> {
+        let needle = needle.to_lowercase();
+        (from..self.entries.len()).find(|&index| {
+            self.get(index)
+                .is_some_and(|entry| entry.to_lowercase().contains(&needle))
+        })
+    }
+}
+
+fn state_dir() -> PathBuf {
+    env::var("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| {
+            Path::new(&env::var("HOME").unwrap_or_else(|_| ".".into())).join(".local/state")
+        })
+        .join("anyrun")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(size: usize) -> History {
+        History {
+            entries: Vec::new(),
+            size,
+            path: PathBuf::new(),
+        }
+    }
+
+    #[test]
+    fn recent_entries_come_first() {
+        let mut history = history(10);
+        history.push("one");
+        history.push("two");
+        assert_eq!(history.get(0), Some("two"));
+        assert_eq!(history.get(1), Some("one"));
+        assert_eq!(history.get(2), None);
+    }
+
+    #[test]
+    fn repeated_queries_move_to_the_front() {
+        let mut history = history(10);
+        history.push("one");
+        history.push("two");
+        history.push(" one ");
+        assert_eq!(history.entries, ["two", "one"]);
+    }
+
+    #[test]
+    fn oldest_entries_are_dropped() {
+        let mut history = history(2);
+        for query in ["one", "two", "three"] {
+            history.push(query);
+        }
+        assert_eq!(history.entries, ["two", "three"]);
+    }
+
+    #[test]
+    fn empty_queries_and_disabled_history_are_ignored() {
+        let mut disabled = history(0);
+        disabled.push("one");
+        assert!(disabled.is_empty());
+
+        let mut history = history(10);
+        history.push("  ");
+        assert!(history.is_empty());
+    }
+
+    #[test]
+    fn search_continues_with_older_entries() {
+        let mut history = history(10);
+        for query in ["Firefox", "files", "fire"] {
+            history.push(query);
+        }
+        assert_eq!(history.search("FIR", 0), Some(0));
+        assert_eq!(history.search("fir", 1), Some(2));
+        assert_eq!(history.search("fir", 3), None);
+        assert_eq!(history.search("nothing", 0), None);
+    }
+}
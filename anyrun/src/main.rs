@@ -1,21 +1,32 @@
 use clap::{Parser, Subcommand};
 use gtk4::{self as gtk, gio, glib, prelude::*};
-use relm4::Sender;
+use relm4::Controller;
 use serde::{Deserialize, Serialize};
 use std::{
     cell::RefCell,
+    collections::HashMap,
     io::{self, IsTerminal, Read, Write},
+    path::PathBuf,
     rc::Rc,
     sync::Arc,
 };
 
 mod app;
+mod check;
 mod config;
-mod plugin_box;
+mod dmenu;
+mod history;
+mod plugin;
+mod preview;
 mod provider;
-use crate::config::ConfigArgs;
+mod query;
+mod results;
+use crate::{config::ConfigArgs, dmenu::DmenuArgs, plugin::PluginCommand, query::QueryArgs};
 use gio::prelude::DBusMethodCall;
 
+const OBJECT_PATH: &str = "/org/anyrun/anyrun";
+const INTERFACE_NAME: &str = "org.anyrun.Anyrun";
+
 const INTERFACE_XML: &str = r#"
 <node>
     <interface name="org.anyrun.Anyrun">
@@ -23,8 +34,36 @@ const INTERFACE_XML: &str = r#"
             <arg type="ay" name="args" direction="in"/>
             <arg type="ay" name="result" direction="out"/>
         </method>
+        <method name="Toggle">
+            <arg type="ay" name="args" direction="in"/>
+            <arg type="ay" name="result" direction="out"/>
+        </method>
+        <method name="ShowWithQuery">
+            <arg type="s" name="text" direction="in"/>
+        </method>
+        <method name="SetQuery">
+            <arg type="s" name="text" direction="in"/>
+        </method>
+        <method name="LoadPlugin">
+            <arg type="s" name="path" direction="in"/>
+            <arg type="s" name="name" direction="out"/>
+        </method>
+        <method name="UnloadPlugin">
+            <arg type="s" name="name" direction="in"/>
+            <arg type="b" name="unloaded" direction="out"/>
+        </method>
+        <method name="ListPlugins">
+            <arg type="a(ss)" name="plugins" direction="out"/>
+        </method>
         <method name="Close"></method>
         <method name="Quit"></method>
+        <property name="Visible" type="b" access="read"/>
+        <signal name="Shown"></signal>
+        <signal name="Closed"></signal>
+        <signal name="Selected">
+            <arg type="s" name="plugin"/>
+            <arg type="s" name="title"/>
+        </signal>
     </interface>
 </node>
 "#;
@@ -36,6 +75,17 @@ struct Show {
 
 enum InterfaceMethod {
     Show(Show),
+    /// Closes Anyrun if it is shown, shows it otherwise
+    Toggle(Show),
+    /// Shows Anyrun with the entry prefilled, or replaces the query if it is already shown
+    ShowWithQuery(String),
+    SetQuery(String),
+    /// Loads a plugin into the providers of the shown windows, replacing the one with the
+    /// same name
+    LoadPlugin(PathBuf),
+    UnloadPlugin(String),
+    /// The names and paths of the plugins of the shown Anyrun
+    ListPlugins,
     Close,
     Quit,
 }
@@ -52,6 +102,27 @@ impl DBusMethodCall for InterfaceMethod {
                 .get::<Show>()
                 .map(Self::Show)
                 .ok_or_else(|| glib::Error::new(gio::DBusError::InvalidArgs, "Invalid args")),
+            "Toggle" => params
+                .get::<Show>()
+                .map(Self::Toggle)
+                .ok_or_else(|| glib::Error::new(gio::DBusError::InvalidArgs, "Invalid args")),
+            "ShowWithQuery" => params
+                .get::<(String,)>()
+                .map(|(text,)| Self::ShowWithQuery(text))
+                .ok_or_else(|| glib::Error::new(gio::DBusError::InvalidArgs, "Invalid args")),
+            "SetQuery" => params
+                .get::<(String,)>()
+                .map(|(text,)| Self::SetQuery(text))
+                .ok_or_else(|| glib::Error::new(gio::DBusError::InvalidArgs, "Invalid args")),
+            "LoadPlugin" => params
+                .get::<(String,)>()
+                .map(|(path,)| Self::LoadPlugin(path.into()))
+                .ok_or_else(|| glib::Error::new(gio::DBusError::InvalidArgs, "Invalid args")),
+            "UnloadPlugin" => params
+                .get::<(String,)>()
+                .map(|(name,)| Self::UnloadPlugin(name))
+                .ok_or_else(|| glib::Error::new(gio::DBusError::InvalidArgs, "Invalid args")),
+            "ListPlugins" => Ok(Self::ListPlugins),
             "Close" => Ok(Self::Close),
             "Quit" => Ok(Self::Quit),
             _ => Err(glib::Error::new(
@@ -67,27 +138,69 @@ impl DBusMethodCall for InterfaceMethod {
 pub struct Args {
     #[arg(short, long)]
     config_dir: Option<String>,
+    /// Name of the profile in the config to apply
+    #[arg(long)]
+    profile: Option<String>,
     #[command(flatten)]
     config: ConfigArgs,
+    #[command(flatten)]
+    dmenu: DmenuArgs,
     #[command(subcommand)]
     command: Option<Command>,
 }
 
-#[derive(Subcommand, Debug, Clone, Copy, Deserialize, Serialize)]
+impl Args {
+    /// The query to prefill the entry with, from `anyrun show --query`
+    pub fn query(&self) -> Option<String> {
+        match &self.command {
+            Some(Command::Show { query }) => query.clone(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Subcommand, Debug, Clone, Deserialize, Serialize)]
 enum Command {
     Daemon,
+    /// Show Anyrun, this is also done without a subcommand
+    Show {
+        /// Text to prefill the entry with
+        #[arg(long)]
+        query: Option<String>,
+    },
+    /// Close Anyrun if the daemon is showing it, show it otherwise
+    Toggle,
     Close,
     Quit,
+    /// Check the config for problems, exiting with a non-zero code if any are found
+    CheckConfig,
+    /// Query the plugins without showing Anyrun, and print the matches
+    Query(QueryArgs),
+    /// Load, unload or list the plugins of the Anyrun shown by the daemon
+    Plugin {
+        #[command(subcommand)]
+        command: PluginCommand,
+    },
 }
 
 struct DaemonState {
-    sender: Option<Sender<app::AppMsg>>,
+    /// The shown windows, each with its own plugins and provider, the newest one last.
+    /// A window is shut down once it is removed from the application.
+    windows: Vec<Controller<app::App>>,
+}
+
+impl DaemonState {
+    fn close_all(&self) {
+        for window in &self.windows {
+            window.emit(app::AppMsg::Action(config::Action::Close));
+        }
+    }
 }
 
 fn main() {
     let args = Args::parse();
 
-    if let Some(cmd) = args.command {
+    if let Some(cmd) = &args.command {
         match cmd {
             Command::Close | Command::Quit => {
                 fast_ipc_call(if matches!(cmd, Command::Close) {
@@ -97,13 +210,43 @@ fn main() {
                 });
                 return;
             }
+            Command::Show { .. } => (),
+            Command::Toggle => {
+                run_client(args, "Toggle");
+                return;
+            }
             Command::Daemon => {
                 run_daemon(args);
                 return;
             }
+            Command::CheckConfig => {
+                if !check::check_config(&args) {
+                    std::process::exit(1);
+                }
+                return;
+            }
+            Command::Query(query) => {
+                if !query::query(&args, query) {
+                    std::process::exit(1);
+                }
+                return;
+            }
+            Command::Plugin { command } => {
+                if !plugin::plugin_command(command) {
+                    std::process::exit(1);
+                }
+                return;
+            }
         }
     }
-    run_client(args);
+    run_client(args, "Show");
+}
+
+/// Emits a signal of the daemon's interface, which is only listened to so failing is fine
+pub fn emit_signal(conn: &gio::DBusConnection, signal: &str, params: Option<&glib::Variant>) {
+    if let Err(why) = conn.emit_signal(None, OBJECT_PATH, INTERFACE_NAME, signal, params) {
+        eprintln!("[anyrun] Failed to emit the {signal} signal: {why}");
+    }
 }
 
 fn fast_ipc_call(method: &'static str) {
@@ -114,8 +257,8 @@ fn fast_ipc_call(method: &'static str) {
             if let Ok(conn) = res {
                 conn.call(
                     Some("org.anyrun.anyrun"),
-                    "/org/anyrun/anyrun",
-                    "org.anyrun.Anyrun",
+                    OBJECT_PATH,
+                    INTERFACE_NAME,
                     method,
                     None,
                     None,
@@ -129,7 +272,9 @@ fn fast_ipc_call(method: &'static str) {
     );
 }
 
-fn run_client(args: Args) {
+/// Shows Anyrun through `method`, which is `Show` or `Toggle`, if the daemon is running.
+/// Otherwise it is shown by this process.
+fn run_client(args: Args, method: &'static str) {
     let app = gtk::Application::new(Some("org.anyrun.anyrun"), gio::ApplicationFlags::FLAGS_NONE);
     if let Err(e) = app.register(None::<&gio::Cancellable>) {
         eprintln!("Registration error: {e}");
@@ -162,9 +307,9 @@ fn run_client(args: Args) {
 
         conn.call(
             Some("org.anyrun.anyrun"),
-            "/org/anyrun/anyrun",
-            "org.anyrun.Anyrun",
-            "Show",
+            OBJECT_PATH,
+            INTERFACE_NAME,
+            method,
             Some(&msg),
             None,
             gio::DBusCallFlags::NONE,
@@ -173,12 +318,14 @@ fn run_client(args: Args) {
             move |res| {
                 if let Ok(val) = res {
                     if let Some(b) = val.child_value(0).get::<Vec<u8>>() {
-                        if let Ok(app::PostRunAction::Stdout(out_data)) =
-                            serde_json::from_slice::<app::PostRunAction>(&b)
-                        {
-                            let mut out = io::stdout().lock();
-                            let _ = out.write_all(&out_data);
-                            let _ = out.flush();
+                        match serde_json::from_slice::<app::PostRunAction>(&b) {
+                            Ok(app::PostRunAction::Stdout(out_data)) => {
+                                let mut out = io::stdout().lock();
+                                let _ = out.write_all(&out_data);
+                                let _ = out.flush();
+                            }
+                            Ok(app::PostRunAction::Cancelled) => std::process::exit(1),
+                            _ => (),
                         }
                     }
                 }
@@ -190,59 +337,185 @@ fn run_client(args: Args) {
         let shared_init = Arc::new(read_init_data());
 
         app.connect_activate(move |app| {
-            app::App::launch(app, (*shared_init).clone(), None);
+            // Lives as long as the process, which exits once it is closed
+            app::App::launch(app, (*shared_init).clone(), app::Caller::Local).detach_runtime();
         });
         app.run_with_args(&Vec::<String>::new());
     }
 }
 
-fn run_daemon(_args: Args) {
+/// Announces that a window was shown or closed by the daemon. `Visible` only changes with
+/// the first window shown and the last one closed.
+fn visibility_changed(conn: &gio::DBusConnection, visible: bool, windows: usize) {
+    emit_signal(conn, if visible { "Shown" } else { "Closed" }, None);
+    if windows != usize::from(visible) {
+        return;
+    }
+    let changed = HashMap::from([("Visible", visible.to_variant())]);
+    if let Err(why) = conn.emit_signal(
+        None,
+        OBJECT_PATH,
+        "org.freedesktop.DBus.Properties",
+        "PropertiesChanged",
+        Some(&(INTERFACE_NAME, changed, Vec::<String>::new()).to_variant()),
+    ) {
+        eprintln!("[anyrun] Failed to emit the PropertiesChanged signal: {why}");
+    }
+}
+
+fn run_daemon(args: Args) {
     let app = gtk::Application::new(Some("org.anyrun.anyrun"), gio::ApplicationFlags::IS_SERVICE);
     app.register(None::<&gio::Cancellable>)
         .expect("Failed to register daemon");
 
     let _hold = app.hold();
-    let state = Rc::new(RefCell::new(DaemonState { sender: None }));
+    let state = Rc::new(RefCell::new(DaemonState {
+        windows: Vec::new(),
+    }));
     let dbus_conn = app
         .dbus_connection()
         .expect("Failed to get DBus connection");
 
     let node_info = gio::DBusNodeInfo::for_xml(INTERFACE_XML).expect("Invalid XML");
-    let interface = node_info.lookup_interface("org.anyrun.Anyrun").unwrap();
+    let interface = node_info.lookup_interface(INTERFACE_NAME).unwrap();
+
+    // Every window of the daemon is a shown instance of Anyrun
+    app.connect_window_added(glib::clone!(
+        #[strong]
+        dbus_conn,
+        move |app, _| visibility_changed(&dbus_conn, true, app.windows().len())
+    ));
+    app.connect_window_removed(glib::clone!(
+        #[strong]
+        dbus_conn,
+        #[strong]
+        state,
+        move |app, window| {
+            let mut state = state.borrow_mut();
+            if let Some(i) = state
+                .windows
+                .iter()
+                .position(|shown| shown.widget() == window)
+            {
+                let controller = state.windows.remove(i);
+                // Shuts down only this window, once it is done handling the message that
+                // closed it
+                glib::idle_add_local_once(move || drop(controller));
+            }
+            visibility_changed(&dbus_conn, false, app.windows().len());
+        }
+    ));
 
     dbus_conn
-        .register_object("/org/anyrun/anyrun", &interface)
+        .register_object(OBJECT_PATH, &interface)
         .typed_method_call::<InterfaceMethod>()
         .invoke(glib::clone!(
             #[weak]
             app,
             #[strong]
             state,
-            move |_, _, method, invocation| {
-                match method {
-                    InterfaceMethod::Show(show) => match serde_json::from_slice(&show.args) {
+            move |conn, _, method, invocation| {
+                let show = |params: Show, invocation: gio::DBusMethodInvocation| {
+                    match serde_json::from_slice(&params.args) {
                         Ok(init_data) => {
-                            state.borrow_mut().sender =
-                                Some(app::App::launch(&app, init_data, Some(invocation)));
+                            let window = app::App::launch(
+                                &app,
+                                init_data,
+                                app::Caller::Invocation(invocation),
+                            );
+                            state.borrow_mut().windows.push(window);
                         }
                         Err(_) => {
                             invocation.return_error(gio::DBusError::InvalidArgs, "Invalid JSON");
                         }
+                    }
+                };
+                let newest = state
+                    .borrow()
+                    .windows
+                    .last()
+                    .map(|window| window.sender().clone());
+                let not_shown = |invocation: gio::DBusMethodInvocation| {
+                    invocation.return_error(gio::DBusError::Failed, "Anyrun isn't shown");
+                };
+
+                match method {
+                    InterfaceMethod::Show(params) => show(params, invocation),
+                    InterfaceMethod::Toggle(params) => match newest {
+                        Some(_) => {
+                            state.borrow().close_all();
+                            invocation.return_value(Some(
+                                &(serde_json::to_vec(&app::PostRunAction::None).unwrap(),)
+                                    .to_variant(),
+                            ));
+                        }
+                        None => show(params, invocation),
                     },
-                    InterfaceMethod::Close => {
-                        if let Some(s) = &state.borrow().sender {
-                            s.emit(app::AppMsg::Action(config::Action::Close));
+                    InterfaceMethod::ShowWithQuery(text) => {
+                        match newest {
+                            Some(sender) => sender.emit(app::AppMsg::SetQuery(text)),
+                            None => {
+                                // Shown with the options the daemon was started with
+                                let mut args = args.clone();
+                                args.command = Some(Command::Show { query: Some(text) });
+                                let init_data = app::AppInit {
+                                    args,
+                                    stdin: Vec::new(),
+                                    env: std::env::vars().collect(),
+                                };
+                                let window =
+                                    app::App::launch(&app, init_data, app::Caller::Daemon(conn));
+                                state.borrow_mut().windows.push(window);
+                            }
+                        }
+                        invocation.return_value(None);
+                    }
+                    InterfaceMethod::SetQuery(text) => {
+                        if let Some(sender) = newest {
+                            sender.emit(app::AppMsg::SetQuery(text));
+                        }
+                        invocation.return_value(None);
+                    }
+                    // Every window takes the plugin, the newest one answers
+                    InterfaceMethod::LoadPlugin(path) => match newest {
+                        Some(sender) => {
+                            for window in state.borrow().windows.iter().rev().skip(1) {
+                                window.emit(app::AppMsg::LoadPlugin(path.clone(), None));
+                            }
+                            sender.emit(app::AppMsg::LoadPlugin(path, Some(invocation)));
                         }
-                        state.borrow_mut().sender = None;
+                        None => not_shown(invocation),
+                    },
+                    InterfaceMethod::UnloadPlugin(name) => match newest {
+                        Some(sender) => {
+                            for window in state.borrow().windows.iter().rev().skip(1) {
+                                window.emit(app::AppMsg::UnloadPlugin(name.clone(), None));
+                            }
+                            sender.emit(app::AppMsg::UnloadPlugin(name, Some(invocation)));
+                        }
+                        None => not_shown(invocation),
+                    },
+                    InterfaceMethod::ListPlugins => match newest {
+                        Some(sender) => sender.emit(app::AppMsg::ListPlugins(invocation)),
+                        None => not_shown(invocation),
+                    },
+                    InterfaceMethod::Close => {
+                        state.borrow().close_all();
                         invocation.return_value(None);
                     }
                     InterfaceMethod::Quit => {
+                        provider::shutdown();
                         invocation.return_value(None);
                         app.quit();
                     }
                 }
             }
         ))
+        .property(glib::clone!(
+            #[strong]
+            state,
+            move |_, _, _, _, _| (!state.borrow().windows.is_empty()).to_variant()
+        ))
         .build()
         .expect("Failed to register object");
 
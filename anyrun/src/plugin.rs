@@ -0,0 +1,86 @@
+use std::{fs, path::PathBuf};
+
+use clap::Subcommand;
+use gtk4::{gio, glib, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{INTERFACE_NAME, OBJECT_PATH};
+
+/// The subcommands of `anyrun plugin`
+#[derive(Subcommand, Clone, Debug, Serialize, Deserialize)]
+pub enum PluginCommand {
+    /// Load a plugin, replacing the one with the same name. Resolved like the `plugins` in
+    /// the config, unless it is the path of an existing file.
+    Load { path: PathBuf },
+    /// Unload the plugin with the name
+    Unload { name: String },
+    /// Print the name and path of every plugin
+    List,
+}
+
+/// Passes the command to the daemon and prints its answer. Returns whether it succeeded.
+pub fn plugin_command(command: &PluginCommand) -> bool {
+    let (method, params) = match command {
+        PluginCommand::Load { path } => {
+            // Relative to where the command runs, rather than to the daemon
+            let path = fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+            (
+                "LoadPlugin",
+                Some((path.to_string_lossy().into_owned(),).to_variant()),
+            )
+        }
+        PluginCommand::Unload { name } => ("UnloadPlugin", Some((name.clone(),).to_variant())),
+        PluginCommand::List => ("ListPlugins", None),
+    };
+
+    let reply =
+        gio::bus_get_sync(gio::BusType::Session, None::<&gio::Cancellable>).and_then(|conn| {
+            conn.call_sync(
+                Some("org.anyrun.anyrun"),
+                OBJECT_PATH,
+                INTERFACE_NAME,
+                method,
+                params.as_ref(),
+                None,
+                gio::DBusCallFlags::NO_AUTO_START,
+                -1,
+                None::<&gio::Cancellable>,
+            )
+        });
+    let reply = match reply {
+        Ok(reply) => reply,
+        Err(why) => {
+            eprintln!("[anyrun] {method} failed: {why}");
+            return false;
+        }
+    };
+
+    match command {
+        PluginCommand::Load { .. } => match reply.get::<(String,)>() {
+            Some((name,)) => println!("Loaded '{name}'"),
+            None => return invalid_reply(&reply),
+        },
+        PluginCommand::Unload { name } => match reply.get::<(bool,)>() {
+            Some((true,)) => println!("Unloaded '{name}'"),
+            Some((false,)) => {
+                eprintln!("No plugin named '{name}' is loaded");
+                return false;
+            }
+            None => return invalid_reply(&reply),
+        },
+        PluginCommand::List => match reply.get::<(Vec<(String, String)>,)>() {
+            Some((plugins,)) => {
+                for (name, path) in plugins {
+                    println!("{name}\t{path}");
+                }
+            }
+            None => return invalid_reply(&reply),
+        },
+    }
+    true
+}
+
+fn invalid_reply(reply: &glib::Variant) -> bool {
+    eprintln!("[anyrun] Unexpected reply from the daemon: {reply}");
+    false
+}
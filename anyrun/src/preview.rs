@@ -0,0 +1,135 @@
+use std::{cell::Cell, fs, io::Read, path::Path};
+
+use anyrun_provider_ipc::Preview;
+use gtk::{gdk, gio, glib, prelude::*};
+use gtk4 as gtk;
+
+/// How much of a text file is shown, previews are only meant to tell files apart
+const MAX_FILE_BYTES: u64 = 64 * 1024;
+/// How many entries of a directory are listed
+const MAX_DIR_ENTRIES: usize = 200;
+
+thread_local! {
+    /// Bumped by every `show`, so files still loading for an earlier preview are dropped
+    static GENERATION: Cell<u64> = const { Cell::new(0) };
+}
+
+/// A file read off the main thread, ready to be shown
+enum Loaded {
+    Text(String),
+    Texture(gdk::Texture),
+}
+
+/// Fills the preview pane, showing either the text or the picture
+pub fn show(text: &gtk::Label, picture: &gtk::Picture, preview: Option<Preview>) {
+    text.set_use_markup(false);
+    text.set_label("");
+    text.set_visible(false);
+    picture.set_paintable(Option::<&gdk::Paintable>::None);
+    picture.set_visible(false);
+    GENERATION.set(GENERATION.get().wrapping_add(1));
+
+    match preview {
+        Some(Preview::Text(content)) => show_text(text, &content, false),
+        Some(Preview::Markup(content)) => show_text(text, &content, true),
+        Some(Preview::Image(image)) => show_image(text, picture, &image),
+        Some(Preview::File(path)) => load(text, picture, move || load_file(Path::new(&path))),
+        None => (),
+    }
+}
+
+fn show_text(text: &gtk::Label, content: &str, markup: bool) {
+    text.set_use_markup(markup);
+    text.set_label(content);
+    text.set_visible(true);
+}
+
+fn show_image(text: &gtk::Label, picture: &gtk::Picture, image: &str) {
+    if Path::new(image).is_absolute() {
+        let image = image.to_string();
+        load(text, picture, move || load_image(Path::new(&image)));
+    } else {
+        let icon = gtk::IconTheme::for_display(&WidgetExt::display(picture)).lookup_icon(
+            image,
+            &[],
+            256,
+            1,
+            gtk::TextDirection::None,
+            gtk::IconLookupFlags::empty(),
+        );
+        picture.set_paintable(Some(&icon));
+        picture.set_visible(true);
+    }
+}
+
+/// Reads a file on a blocking thread and shows it once done, unless the preview has changed
+/// in the meantime
+fn load(text: &gtk::Label, picture: &gtk::Picture, read: impl FnOnce() -> Loaded + Send + 'static) {
+    let generation = GENERATION.get();
+    let (text, picture) = (text.clone(), picture.clone());
+    glib::spawn_future_local(async move {
+        let Ok(loaded) = gio::spawn_blocking(read).await else {
+            return;
+        };
+        if GENERATION.get() != generation {
+            return;
+        }
+        match loaded {
+            Loaded::Text(content) => show_text(&text, &content, false),
+            Loaded::Texture(texture) => {
+                picture.set_paintable(Some(&texture));
+                picture.set_visible(true);
+            }
+        }
+    });
+}
+
+fn load_image(path: &Path) -> Loaded {
+    if !path.exists() {
+        return Loaded::Text(format!("{} does not exist", path.display()));
+    }
+    match gdk::Texture::from_filename(path) {
+        Ok(texture) => Loaded::Texture(texture),
+        Err(why) => Loaded::Text(format!("Failed to load image: {why}")),
+    }
+}
+
+fn load_file(path: &Path) -> Loaded {
+    if path.is_dir() {
+        let mut entries = match fs::read_dir(path) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| {
+                    let mut name = entry.file_name().to_string_lossy().into_owned();
+                    if entry.file_type().is_ok_and(|ty| ty.is_dir()) {
+                        name.push('/');
+                    }
+                    name
+                })
+                .collect::<Vec<_>>(),
+            Err(why) => return Loaded::Text(format!("Failed to read directory: {why}")),
+        };
+        entries.sort();
+        entries.truncate(MAX_DIR_ENTRIES);
+        return Loaded::Text(entries.join("\n"));
+    }
+
+    let mime = tree_magic_mini::from_filepath(path).unwrap_or("application/octet-stream");
+
+    if mime.starts_with("image/") {
+        load_image(path)
+    } else if mime.starts_with("text/") || mime == "application/json" || mime == "application/xml" {
+        let mut content = Vec::new();
+        match fs::File::open(path)
+            .and_then(|file| file.take(MAX_FILE_BYTES).read_to_end(&mut content))
+        {
+            Ok(_) => Loaded::Text(String::from_utf8_lossy(&content).into_owned()),
+            Err(why) => Loaded::Text(format!("Failed to read file: {why}")),
+        }
+    } else {
+        let size = fs::metadata(path)
+            .map(|metadata| metadata.len())
+            .unwrap_or_default();
+        Loaded::Text(format!("{mime}\n{size} bytes"))
+    }
+}
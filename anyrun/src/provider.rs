@@ -1,101 +1,363 @@
 use std::{
-    env, fs,
+    env,
+    ffi::{OsStr, OsString},
+    fs,
     io::{self, Write},
     path::PathBuf,
-    process::{Command, Stdio},
-    sync::Arc,
+    process::{self, Child, Command, Stdio},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
 };
 
 use anyrun_provider_ipc as ipc;
 use relm4::Sender;
-use tokio::{net::UnixListener, sync::mpsc::Receiver};
+use tokio::{
+    net::{UnixListener, UnixStream},
+    sync::mpsc::Receiver,
+};
 
 use crate::config::Config;
 
+/// The providers the daemon keeps running between shows, so the plugins don't have to be
+/// loaded and initialized again every time. There is more than one while several windows
+/// are shown at once.
+static WARM_PROVIDERS: Mutex<Vec<WarmProvider>> = Mutex::new(Vec::new());
+
+/// Tells apart the sockets of the providers started by this process
+static SOCKET_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+struct WarmProvider {
+    /// The executable, arguments and environment it was started with, it is only reused with
+    /// the same. Plugins spawn programs with the environment of the provider, which has to
+    /// be the one of the command that shows Anyrun.
+    provider: PathBuf,
+    args: Vec<OsString>,
+    env: Vec<(String, String)>,
+    child: Child,
+    socket_path: String,
+    /// The provider serves one client at a time, others start their own
+    in_use: bool,
+}
+
+/// Marks the warm provider with the socket path as free again once the connection to it is
+/// done with
+struct WarmGuard(String);
+
+impl Drop for WarmGuard {
+    fn drop(&mut self) {
+        if let Some(provider) = WARM_PROVIDERS
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .find(|provider| provider.socket_path == self.0)
+        {
+            provider.in_use = false;
+        }
+    }
+}
+
+/// What a window starts its connection to a provider with
+pub struct WorkerInit {
+    pub config: Arc<Config>,
+    pub config_dir: Option<String>,
+    /// The stdin received by the launching command
+    pub stdin: Vec<u8>,
+    /// The environment of the launching command
+    pub env: Vec<(String, String)>,
+    /// Whether the warm provider can be used, which is only kept by the daemon. Stdin is
+    /// only passed to a provider when it starts, so it needs its own one.
+    pub warm: bool,
+}
+
 pub fn worker(
-    config: Arc<Config>,
-    config_dir: Option<String>,
-    mut rx: Receiver<anyrun_provider_ipc::Request>,
+    init: WorkerInit,
+    rx: Receiver<anyrun_provider_ipc::Request>,
     sender: Sender<anyrun_provider_ipc::Response>,
-    // The stdin received by the launching command
-    stdin: Vec<u8>,
-    // The environment of the launching command
-    env: Vec<(String, String)>,
 ) -> io::Result<()> {
     tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
         .unwrap()
         .block_on(async {
-            let socket_path = format!(
-                "{}/anyrun.sock",
-                env::var("XDG_RUNTIME_DIR").unwrap_or("/tmp".to_string())
-            );
-            // Make sure that it does not exist already
-            let _ = fs::remove_file(&socket_path);
-            let listener = UnixListener::bind(&socket_path).unwrap();
-
-            let mut child = match Command::new(&config.provider)
-                .stdin(Stdio::piped())
-                .arg("--config-dir")
-                .arg(config_dir.unwrap_or(ipc::CONFIG_DIRS[0].to_string()))
-                .args(
-                    config
-                        .plugins
-                        .iter()
-                        .flat_map(|plugin| [PathBuf::from("-p"), plugin.to_owned()]),
-                )
-                .arg("connect-to")
-                .arg(&socket_path)
-                .envs(env)
-                .spawn()
-            {
-                Ok(child) => child,
-                Err(why) => match why.kind() {
-                    io::ErrorKind::NotFound => {
-                        eprintln!("[anyrun] `{}` Not found, make sure `anyrun-provider` is installed and available in $PATH, \
-                             or configure an alternative path via the `provider` config option.", config.provider.display());
-                        return Ok(());
-                    }
-                    _ => return Err(why),
-                },
-            };
+            let args = provider_args(&init.config, init.config_dir.clone());
+            match run(&init, args, rx, sender).await {
+                Err(why) if why.kind() == io::ErrorKind::NotFound => {
+                    eprintln!("[anyrun] `{}` Not found, make sure `anyrun-provider` is installed and available in $PATH, \
+                         or configure an alternative path via the `provider` config option.", init.config.provider.display());
+                    Ok(())
+                }
+                res => res,
+            }
+        })
+}
 
-            if let Some(mut child_stdin) = child.stdin.take() {
-                child_stdin.write_all(&stdin).unwrap();
-            };
+/// Stops the warm providers, for when the daemon quits
+pub fn shutdown() {
+    for mut provider in WARM_PROVIDERS.lock().unwrap().drain(..) {
+        stop(&mut provider);
+    }
+}
 
-            let (stream, _) = listener.accept().await?;
-            let mut socket = ipc::Socket::new(stream);
+async fn run(
+    init: &WorkerInit,
+    args: Vec<OsString>,
+    mut rx: Receiver<anyrun_provider_ipc::Request>,
+    sender: Sender<anyrun_provider_ipc::Response>,
+) -> io::Result<()> {
+    let warm_stream = if init.warm && init.stdin.is_empty() {
+        connect_warm(&init.config, args.clone(), &init.env).await?
+    } else {
+        None
+    };
+
+    let (stream, own, _guard) = match warm_stream {
+        Some((stream, guard)) => (stream, None, Some(guard)),
+        None => {
+            let (own, stream) =
+                OwnProvider::start(&init.config, &args, &init.stdin, &init.env).await?;
+            (stream, Some(own), None)
+        }
+    };
+    let mut socket = ipc::Socket::new(stream);
 
-            loop {
-                tokio::select! {
-                    req = rx.recv() => {
-                        if let Some(req) = req {
+    loop {
+        tokio::select! {
+            req = rx.recv() => {
+                if let Some(req) = req {
+                    if matches!(req, ipc::Request::Quit) {
+                        // The warm provider is kept for the next show, and resets the plugins
+                        // once this window is gone
+                        let req = if own.is_some() { req } else { ipc::Request::Detach };
                         socket.send(&req).await?;
-                        if matches!(req, ipc::Request::Quit) {
-                            break;
-                        }
-                        }
+                        break;
                     }
-                    res = socket.recv() => {
-                        match res {
+                    socket.send(&req).await?;
+                }
+            }
+            res = socket.recv() => {
+                match res {
                     Ok(response) => sender.emit(response),
                     Err(why) => {
                         eprintln!("[anyrun] Error reading from IPC: {why}");
                         break;
                     },
-                        }
-                    }
                 }
             }
+        }
+    }
+
+    if let Some(own) = own {
+        own.finish();
+    }
+
+    Ok(())
+}
+
+/// A provider started for a single client, which exits once it gets `Request::Quit`
+pub struct OwnProvider {
+    child: Child,
+    socket_path: String,
+}
 
-            // Remove it after we are done with it
-            let _ = fs::remove_file(&socket_path);
-            // Make sure it exits properly and doesn't leave a zombie process
-            let _ = child.wait();
+impl OwnProvider {
+    /// Starts the provider with `args` from `provider_args`, and waits for it to connect
+    pub async fn start(
+        config: &Config,
+        args: &[OsString],
+        stdin: &[u8],
+        env: &[(String, String)],
+    ) -> io::Result<(Self, UnixStream)> {
+        let socket_path = socket_path("anyrun");
+        // Make sure that it does not exist already
+        let _ = fs::remove_file(&socket_path);
+        let listener = UnixListener::bind(&socket_path)?;
 
-            Ok(())
+        let mut child = spawn(config, args, "connect-to", &socket_path, env)?;
+        if let Some(mut child_stdin) = child.stdin.take() {
+            child_stdin.write_all(stdin)?;
+        };
+
+        let (stream, _) = listener.accept().await?;
+        Ok((Self { child, socket_path }, stream))
+    }
+
+    pub fn finish(mut self) {
+        // Remove it after we are done with it
+        let _ = fs::remove_file(&self.socket_path);
+        // Make sure it exits properly and doesn't leave a zombie process
+        let _ = self.child.wait();
+    }
+}
+
+/// Connects to a warm provider started with the same arguments, starting one if there is
+/// none that is free. Returns `None` if one with the same arguments is busy serving
+/// another window, which then starts its own.
+async fn connect_warm(
+    config: &Config,
+    args: Vec<OsString>,
+    env: &[(String, String)],
+) -> io::Result<Option<(UnixStream, WarmGuard)>> {
+    let mut env = env.to_vec();
+    env.sort();
+    let socket_path = {
+        let mut warm = WARM_PROVIDERS.lock().unwrap();
+        let same = |provider: &WarmProvider| {
+            provider.provider == config.provider && provider.args == args && provider.env == env
+        };
+        // Providers that exited, or were started with other arguments or environment and
+        // aren't used anymore, are replaced
+        warm.retain_mut(|provider| {
+            let keep = matches!(provider.child.try_wait(), Ok(None))
+                && (provider.in_use || same(provider));
+            if !keep {
+                stop(provider);
+            }
+            keep
+        });
+        if warm
+            .iter()
+            .any(|provider| same(provider) && provider.in_use)
+        {
+            return Ok(None);
         }
+
+        let i = match warm.iter().position(same) {
+            Some(i) => i,
+            None => {
+                let socket_path = socket_path("anyrun-provider");
+                let _ = fs::remove_file(&socket_path);
+                let mut child = spawn(config, &args, "socket", &socket_path, &env)?;
+                // Nothing is passed on stdin, so the plugins reading it don't wait for it
+                drop(child.stdin.take());
+                warm.push(WarmProvider {
+                    provider: config.provider.clone(),
+                    args,
+                    env,
+                    child,
+                    socket_path,
+                    in_use: false,
+                });
+                warm.len() - 1
+            }
+        };
+        warm[i].in_use = true;
+        warm[i].socket_path.clone()
+    };
+    let guard = WarmGuard(socket_path.clone());
+
+    // The socket is only created once the plugins are loaded and initialized
+    loop {
+        match UnixStream::connect(&socket_path).await {
+            Ok(stream) => return Ok(Some((stream, guard))),
+            Err(_) => {
+                let exited = WARM_PROVIDERS
+                    .lock()
+                    .unwrap()
+                    .iter_mut()
+                    .find(|provider| provider.socket_path == socket_path)
+                    .is_none_or(|provider| !matches!(provider.child.try_wait(), Ok(None)));
+                if exited {
+                    return Err(io::Error::other(
+                        "The provider exited before accepting connections",
+                    ));
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        }
+    }
+}
+
+fn stop(provider: &mut WarmProvider) {
+    let _ = provider.child.kill();
+    let _ = provider.child.wait();
+    let _ = fs::remove_file(&provider.socket_path);
+}
+
+/// A socket path unique to this process and connection, so several clients and windows
+/// don't replace each other's sockets
+fn socket_path(name: &str) -> String {
+    format!(
+        "{}/{name}-{}-{}.sock",
+        env::var("XDG_RUNTIME_DIR").unwrap_or("/tmp".to_string()),
+        process::id(),
+        SOCKET_COUNT.fetch_add(1, Ordering::Relaxed)
     )
 }
+
+/// The arguments the provider is started with, apart from how it connects
+pub fn provider_args(config: &Config, config_dir: Option<String>) -> Vec<OsString> {
+    let mut args: Vec<OsString> = vec![
+        "--config-dir".into(),
+        config_dir.unwrap_or(ipc::CONFIG_DIRS[0].to_string()).into(),
+    ];
+    args.extend(
+        config
+            .plugins
+            .iter()
+            .flat_map(|plugin| [PathBuf::from("-p"), plugin.to_owned()])
+            .map(OsString::from),
+    );
+    if !config.frecency {
+        args.push("--no-frecency".into());
+    }
+    args.push("--default-timeout".into());
+    args.push(config.plugin_timeout.to_string().into());
+    if config.isolate_plugins {
+        args.push("--isolate".into());
+        for group in &config.plugin_groups {
+            let group: Vec<_> = group.iter().map(|plugin| plugin.as_os_str()).collect();
+            args.push("--isolate-group".into());
+            args.push(group.join(OsStr::new(",")));
+        }
+    }
+    args.extend(
+        config
+            .frecency_exclude
+            .iter()
+            .flat_map(|plugin| ["--frecency-exclude", plugin.as_str()])
+            .map(OsString::from),
+    );
+    // Sorted so the arguments stay the same between shows
+    let mut plugin_settings: Vec<_> = config.plugin_settings.iter().collect();
+    plugin_settings.sort_by_key(|(name, _)| *name);
+    args.extend(
+        plugin_settings
+            .into_iter()
+            .flat_map(|(name, settings)| {
+                let max_entries = settings
+                    .max_entries
+                    .map(|count| ["--max-entries".to_string(), format!("{name}={count}")]);
+                let skip_empty_query = (!settings.empty_query)
+                    .then(|| ["--skip-empty-query".to_string(), name.clone()]);
+                let timeout = settings
+                    .timeout
+                    .map(|ms| ["--timeout".to_string(), format!("{name}={ms}")]);
+                max_entries
+                    .into_iter()
+                    .chain(skip_empty_query)
+                    .chain(timeout)
+                    .flatten()
+            })
+            .map(OsString::from),
+    );
+    args
+}
+
+fn spawn(
+    config: &Config,
+    args: &[OsString],
+    mode: &str,
+    socket_path: &str,
+    env: &[(String, String)],
+) -> io::Result<Child> {
+    Command::new(&config.provider)
+        .stdin(Stdio::piped())
+        .args(args)
+        .arg(mode)
+        .arg(socket_path)
+        .envs(env.iter().cloned())
+        .spawn()
+}
@@ -0,0 +1,213 @@
+use std::io::{self, IsTerminal, Read, Write};
+
+use anyrun_interface::{HandleResult, Match, PluginInfo};
+use anyrun_provider_ipc as ipc;
+use clap::Args as ClapArgs;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{self, Config},
+    provider::{self, OwnProvider},
+    Args,
+};
+
+/// The options of `anyrun query`
+#[derive(ClapArgs, Clone, Debug, Serialize, Deserialize)]
+pub struct QueryArgs {
+    /// The text to query the plugins with
+    pub text: String,
+    /// Only print the matches of the plugin with this name
+    #[arg(long)]
+    pub plugin: Option<String>,
+    /// Print the matches as JSON
+    #[arg(long)]
+    pub json: bool,
+    /// Select the match at this index of the printed list, printing what its plugin outputs
+    /// instead of the matches
+    #[arg(long)]
+    pub handle: Option<usize>,
+}
+
+/// A match as printed with `--json`
+#[derive(Serialize)]
+struct JsonMatch<'a> {
+    plugin: &'a str,
+    title: &'a str,
+    description: Option<&'a str>,
+    icon: Option<&'a str>,
+    id: Option<u64>,
+}
+
+/// Queries the plugins without showing Anyrun. Returns whether it succeeded.
+pub fn query(args: &Args, query: &QueryArgs) -> bool {
+    let config_dir = config::config_dir(args.config_dir.clone());
+    let (mut config, warning) = Config::load(config_dir.as_deref(), args.profile.as_deref());
+    if let Some(warning) = warning {
+        eprintln!("[anyrun] {warning}");
+    }
+    config.merge_opt(args.config.clone());
+
+    let mut stdin = Vec::new();
+    if !io::stdin().is_terminal() {
+        io::stdin()
+            .lock()
+            .take(2 * 1024 * 1024)
+            .read_to_end(&mut stdin)
+            .ok();
+    }
+    let env: Vec<(String, String)> = std::env::vars().collect();
+
+    let res = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            let provider_args = provider::provider_args(&config, config_dir);
+            let (provider, stream) =
+                OwnProvider::start(&config, &provider_args, &stdin, &env).await?;
+            let mut socket = ipc::Socket::new(stream);
+            let res = run(&mut socket, query).await;
+            socket.send(&ipc::Request::Quit).await?;
+            provider.finish();
+            res
+        });
+
+    match res {
+        Ok(ok) => ok,
+        Err(why) => {
+            eprintln!(
+                "[anyrun] Failed to query `{}`: {why}",
+                config.provider.display()
+            );
+            false
+        }
+    }
+}
+
+async fn run(socket: &mut ipc::Socket, query: &QueryArgs) -> io::Result<bool> {
+    // Plugins that failed to load are printed by the provider
+    let ipc::Response::Ready { info, .. } = socket.recv().await? else {
+        return Err(io::Error::other("Expected the provider to be ready"));
+    };
+    if let Some(name) = &query.plugin {
+        if !info.iter().any(|info| info.name.as_str() == name) {
+            let names: Vec<_> = info.iter().map(|info| info.name.as_str()).collect();
+            eprintln!(
+                "No plugin named `{name}`, the loaded plugins are: {}",
+                names.join(", ")
+            );
+            return Ok(false);
+        }
+    }
+
+    socket
+        .send(&ipc::Request::Query {
+            text: query.text.clone(),
+        })
+        .await?;
+
+    // Every plugin answers in whatever order they finish, streaming plugins in several
+    // batches until one that isn't partial
+    let mut plugins: Vec<Vec<Match>> = vec![Vec::new(); info.len()];
+    let mut done = vec![false; info.len()];
+    while done.contains(&false) {
+        match socket.recv().await? {
+            ipc::Response::Matches {
+                plugin,
+                matches,
+                append,
+                partial,
+                ..
+            } => {
+                if let Some(i) = info.iter().position(|info| info.name == plugin.name) {
+                    if !append {
+                        plugins[i].clear();
+                    }
+                    plugins[i].extend(matches);
+                    done[i] = !partial;
+                }
+            }
+            ipc::Response::PluginTimedOut { plugin, elapsed_ms } => {
+                eprintln!(
+                    "[anyrun] Plugin '{}' timed out after {elapsed_ms}ms",
+                    plugin.name
+                );
+            }
+            // It won't answer anymore, the matches it would have had are left out
+            ipc::Response::PluginCrashed { plugin } => {
+                eprintln!("[anyrun] Plugin '{}' crashed", plugin.name);
+                if let Some(i) = info.iter().position(|info| info.name == plugin.name) {
+                    done[i] = true;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    let matches: Vec<(&PluginInfo, Match)> = info
+        .iter()
+        .zip(plugins)
+        .filter(|(info, _)| {
+            query
+                .plugin
+                .as_ref()
+                .is_none_or(|name| info.name.as_str() == name)
+        })
+        .flat_map(|(info, matches)| matches.into_iter().map(move |m| (info, m)))
+        .collect();
+
+    let Some(index) = query.handle else {
+        print_matches(&matches, query.json)?;
+        return Ok(true);
+    };
+    let Some((plugin, selection)) = matches.get(index) else {
+        eprintln!("No match at index {index}, there are {}", matches.len());
+        return Ok(false);
+    };
+
+    socket
+        .send(&ipc::Request::Handle {
+            plugin: (*plugin).clone(),
+            selection: selection.clone(),
+            action: None,
+        })
+        .await?;
+    loop {
+        if let ipc::Response::Handled { result, .. } = socket.recv().await? {
+            // There is no clipboard to copy to, so it is printed like the output
+            if let HandleResult::Stdout(bytes) | HandleResult::Copy(bytes) = result {
+                let mut out = io::stdout().lock();
+                out.write_all(&bytes)?;
+                out.flush()?;
+            }
+            return Ok(true);
+        }
+    }
+}
+
+fn print_matches(matches: &[(&PluginInfo, Match)], json: bool) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    if json {
+        let matches: Vec<_> = matches
+            .iter()
+            .map(|(plugin, m)| JsonMatch {
+                plugin: plugin.name.as_str(),
+                title: m.title.as_str(),
+                description: m.description.as_ref().map(|s| s.as_str()).into_option(),
+                icon: m.icon.as_ref().map(|s| s.as_str()).into_option(),
+                id: m.id.into_option(),
+            })
+            .collect();
+        serde_json::to_writer_pretty(&mut out, &matches).map_err(io::Error::other)?;
+        writeln!(out)?;
+    } else {
+        for (i, (plugin, m)) in matches.iter().enumerate() {
+            write!(out, "{i}\t{}\t{}", plugin.name, m.title)?;
+            if let Some(description) = m.description.as_ref().into_option() {
+                write!(out, "\t{description}")?;
+            }
+            writeln!(out)?;
+        }
+    }
+    out.flush()
+}
@@ -0,0 +1,845 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    path::PathBuf,
+    rc::Rc,
+};
+
+use abi_stable::std_types::{ROption, RString};
+use anyrun_interface::{Match, PluginInfo};
+use anyrun_provider_ipc::MatchAction;
+use gtk::{gio, glib, pango, prelude::*};
+use gtk4 as gtk;
+
+#[derive(Clone)]
+pub struct PluginMatch {
+    /// The plugin that provided this match
+    pub plugin_info: PluginInfo,
+    pub content: Match,
+    /// Relevance as reported by the provider, in the range `0.0..=1.0`
+    pub score: f64,
+    /// Secondary actions the plugin offers for this match
+    pub actions: Vec<MatchAction>,
+}
+
+// `Match` and `MatchAction` don't implement `PartialEq`, so compare what is shown
+impl PartialEq for PluginMatch {
+    fn eq(&self, other: &Self) -> bool {
+        self.plugin_info == other.plugin_info
+            && self.content.title == other.content.title
+            && self.content.description == other.content.description
+            && self.content.icon == other.content.icon
+            && self.content.use_pango == other.content.use_pango
+            && self.content.id == other.content.id
+            && self.score == other.score
+            && self.actions.len() == other.actions.len()
+            && self.actions.iter().zip(&other.actions).all(|(a, b)| {
+                a.id == b.id && a.title == b.title && a.icon == b.icon && a.keybind == b.keybind
+            })
+    }
+}
+
+impl PluginMatch {
+    /// Whether both are the same match of the same plugin, even if their relevance or
+    /// actions have changed since
+    pub fn same(&self, other: &Self) -> bool {
+        self.plugin_info == other.plugin_info
+            && self.content.title == other.content.title
+            && self.content.description == other.content.description
+            && self.content.id == other.content.id
+    }
+}
+
+/// A row of the result list. `plugin` is the index of the plugin in the order they are shown.
+#[derive(Clone, PartialEq)]
+pub enum Row {
+    /// The plugin info shown above its matches with `ResultMode::Grouped`
+    Header {
+        plugin: usize,
+        info: PluginInfo,
+        /// Amount of matches, shown while collapsed
+        count: usize,
+        collapsed: bool,
+        /// Milliseconds after which the plugin timed out on the current query
+        slow: Option<u64>,
+    },
+    Match {
+        plugin: usize,
+        plugin_match: PluginMatch,
+    },
+    /// Up to `columns` matches shown as tiles, for plugins using `Layout::Grid`
+    Tiles {
+        plugin: usize,
+        matches: Vec<PluginMatch>,
+        columns: usize,
+    },
+}
+
+impl Row {
+    fn plugin(&self) -> usize {
+        match self {
+            Row::Header { plugin, .. } | Row::Match { plugin, .. } | Row::Tiles { plugin, .. } => {
+                *plugin
+            }
+        }
+    }
+
+    /// How many matches the row contains
+    fn len(&self) -> usize {
+        match self {
+            Row::Header { .. } => 0,
+            Row::Match { .. } => 1,
+            Row::Tiles { matches, .. } => matches.len(),
+        }
+    }
+}
+
+/// The matches a row covers, so indices can be mapped to rows without going through the model
+#[derive(Clone, Copy)]
+struct Span {
+    start: usize,
+    len: usize,
+    plugin: usize,
+    tiles: bool,
+}
+
+struct Shared {
+    /// One per row of the model
+    spans: RefCell<Vec<Span>>,
+    selected: Cell<Option<usize>>,
+    icons_hidden: Cell<bool>,
+    plugin_names: Cell<bool>,
+    marked: RefCell<Vec<PluginMatch>>,
+    /// The widgets of the list items currently set up by the list view
+    items: RefCell<HashMap<gtk::ListItem, RowWidgets>>,
+    on_header: Box<dyn Fn(usize)>,
+    on_match: Box<dyn Fn(usize)>,
+}
+
+/// The list of all matches. Only the rows in view have widgets, which are reused as the list is
+/// scrolled, so it stays responsive with thousands of matches.
+pub struct ResultList {
+    view: gtk::ListView,
+    store: gio::ListStore,
+    shared: Rc<Shared>,
+}
+
+impl ResultList {
+    /// `on_header` is called with the plugin index when a plugin header is clicked, and
+    /// `on_match` with the match index when a match is clicked
+    pub fn new(
+        icons_hidden: bool,
+        on_header: impl Fn(usize) + 'static,
+        on_match: impl Fn(usize) + 'static,
+    ) -> Self {
+        let shared = Rc::new(Shared {
+            spans: RefCell::default(),
+            selected: Cell::new(None),
+            icons_hidden: Cell::new(icons_hidden),
+            plugin_names: Cell::new(false),
+            marked: RefCell::default(),
+            items: RefCell::default(),
+            on_header: Box::new(on_header),
+            on_match: Box::new(on_match),
+        });
+
+        let factory = gtk::SignalListItemFactory::new();
+        factory.connect_setup(glib::clone!(
+            #[weak]
+            shared,
+            move |_, item| {
+                let item = item.downcast_ref::<gtk::ListItem>().unwrap();
+                item.set_activatable(false);
+                item.set_selectable(false);
+                item.set_focusable(false);
+                let widgets = RowWidgets::new(&shared, item);
+                item.set_child(Some(&widgets.root));
+                shared.items.borrow_mut().insert(item.clone(), widgets);
+            }
+        ));
+        factory.connect_bind(glib::clone!(
+            #[weak]
+            shared,
+            move |_, item| {
+                let item = item.downcast_ref::<gtk::ListItem>().unwrap();
+                shared.bind(item);
+            }
+        ));
+        factory.connect_teardown(glib::clone!(
+            #[weak]
+            shared,
+            move |_, item| {
+                let item = item.downcast_ref::<gtk::ListItem>().unwrap();
+                shared.items.borrow_mut().remove(item);
+            }
+        ));
+
+        let store = gio::ListStore::new::<glib::BoxedAnyObject>();
+        let view = gtk::ListView::builder()
+            .model(&gtk::NoSelection::new(Some(store.clone())))
+            .factory(&factory)
+            .css_classes(["matches"])
+            .can_focus(false)
+            .hexpand(true)
+            .build();
+
+        Self {
+            view,
+            store,
+            shared,
+        }
+    }
+
+    pub fn widget(&self) -> &gtk::ListView {
+        &self.view
+    }
+
+    /// The amount of matches in the list
+    pub fn len(&self) -> usize {
+        self.shared
+            .spans
+            .borrow()
+            .last()
+            .map_or(0, |span| span.start + span.len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Replaces the rows of the list. Rows that are the same at the start and the end of the
+    /// list are kept, so a plugin sending new matches only updates its own rows.
+    pub fn set_rows(&self, rows: Vec<Row>) {
+        let old_len = self.store.n_items() as usize;
+        let same = |position: usize, row: &Row| {
+            self.store
+                .item(position as u32)
+                .and_downcast::<glib::BoxedAnyObject>()
+                .is_some_and(|object| *object.borrow::<Row>() == *row)
+        };
+
+        let shortest = old_len.min(rows.len());
+        let prefix = (0..shortest).take_while(|&i| same(i, &rows[i])).count();
+        let suffix = (0..shortest - prefix)
+            .take_while(|&i| same(old_len - 1 - i, &rows[rows.len() - 1 - i]))
+            .count();
+
+        let mut start = 0;
+        *self.shared.spans.borrow_mut() = rows
+            .iter()
+            .map(|row| {
+                let span = Span {
+                    start,
+                    len: row.len(),
+                    plugin: row.plugin(),
+                    tiles: matches!(row, Row::Tiles { .. }),
+                };
+                start += span.len;
+                span
+            })
+            .collect();
+        self.shared.selected.set(None);
+
+        let changed = rows.len() - prefix - suffix;
+        let additions = rows
+            .into_iter()
+            .skip(prefix)
+            .take(changed)
+            .map(glib::BoxedAnyObject::new)
+            .collect::<Vec<_>>();
+        self.store.splice(
+            prefix as u32,
+            (old_len - prefix - suffix) as u32,
+            &additions,
+        );
+        self.shared.sync_selection();
+    }
+
+    /// The match at `index`
+    pub fn get(&self, index: usize) -> Option<PluginMatch> {
+        let spans = self.shared.spans.borrow();
+        let position = row_of(&spans, index)?;
+        let object = self
+            .store
+            .item(position as u32)
+            .and_downcast::<glib::BoxedAnyObject>()?;
+        let row = object.borrow::<Row>();
+        match &*row {
+            Row::Header { .. } => None,
+            Row::Match { plugin_match, .. } => Some(plugin_match.clone()),
+            Row::Tiles { matches, .. } => matches.get(index - spans[position].start).cloned(),
+        }
+    }
+
+    /// The index of the plugin the match at `index` is from
+    pub fn plugin_of(&self, index: usize) -> Option<usize> {
+        let spans = self.shared.spans.borrow();
+        row_of(&spans, index).map(|position| spans[position].plugin)
+    }
+
+    /// Marks the match at `index` as selected and scrolls it into view
+    pub fn select(&self, index: usize) {
+        self.shared.selected.set(Some(index));
+        self.shared.sync_selection();
+
+        let position = row_of(&self.shared.spans.borrow(), index);
+        if let Some(position) = position {
+            self.view
+                .scroll_to(position as u32, gtk::ListScrollFlags::NONE, None);
+        }
+    }
+
+    /// The widget of the selected match, if it is in view
+    pub fn selected_widget(&self) -> Option<gtk::Widget> {
+        let selected = self.shared.selected.get()?;
+        let spans = self.shared.spans.borrow();
+        let position = row_of(&spans, selected)?;
+        let items = self.shared.items.borrow();
+        let (_, widgets) = items
+            .iter()
+            .find(|(item, _)| item.position() as usize == position)?;
+
+        if spans[position].tiles {
+            let tiles = widgets.tiles.borrow();
+            let tile = tiles.get(selected - spans[position].start)?;
+            Some(tile.root.clone().upcast())
+        } else {
+            Some(widgets.list.root.clone().upcast())
+        }
+    }
+
+    /// Where Up or Down moves the selection when the match at `index` is a tile, `None` if it
+    /// isn't. Moving past the first or last row leaves the grid.
+    pub fn grid_step(&self, index: usize, down: bool) -> Option<usize> {
+        grid_step(&self.shared.spans.borrow(), index, down)
+    }
+
+    pub fn set_icons_hidden(&self, hidden: bool) {
+        if self.shared.icons_hidden.replace(hidden) != hidden {
+            self.shared.rebind_all();
+        }
+    }
+
+    /// Shows which matches are marked
+    pub fn set_marked(&self, marked: &[PluginMatch]) {
+        *self.shared.marked.borrow_mut() = marked.to_vec();
+        self.shared.rebind_all();
+    }
+
+    /// Whether each match shows the name of its plugin, for when there are no plugin headers
+    pub fn set_plugin_names(&self, shown: bool) {
+        if self.shared.plugin_names.replace(shown) != shown {
+            self.shared.rebind_all();
+        }
+    }
+}
+
+/// The position of the row containing the match at `index`
+fn row_of(spans: &[Span], index: usize) -> Option<usize> {
+    let position = spans.partition_point(|span| span.start + span.len <= index);
+    (position < spans.len()).then_some(position)
+}
+
+/// See `ResultList::grid_step`
+fn grid_step(spans: &[Span], index: usize, down: bool) -> Option<usize> {
+    let total = spans.last().map_or(0, |span| span.start + span.len);
+    let position = row_of(spans, index)?;
+    let span = spans[position];
+    if !span.tiles {
+        return None;
+    }
+
+    let column = index - span.start;
+    let same_grid = |other: &&Span| other.tiles && other.plugin == span.plugin;
+    let index = if down {
+        match spans.get(position + 1).filter(same_grid) {
+            // The last row may not have a tile below the selected one
+            Some(next) => next.start + column.min(next.len - 1),
+            None => span.start + span.len,
+        }
+    } else {
+        match position
+            .checked_sub(1)
+            .and_then(|previous| spans.get(previous))
+            .filter(same_grid)
+        {
+            Some(previous) => previous.start + column.min(previous.len - 1),
+            None => span.start.checked_sub(1).unwrap_or(total - 1),
+        }
+    };
+    Some(index % total)
+}
+
+impl Shared {
+    fn bind(self: &Rc<Self>, item: &gtk::ListItem) {
+        let Some(object) = item.item().and_downcast::<glib::BoxedAnyObject>() else {
+            return;
+        };
+        let items = self.items.borrow();
+        let Some(widgets) = items.get(item) else {
+            return;
+        };
+        let row = object.borrow::<Row>();
+
+        widgets
+            .header
+            .root
+            .set_visible(matches!(*row, Row::Header { .. }));
+        widgets
+            .list
+            .root
+            .set_visible(matches!(*row, Row::Match { .. }));
+        widgets.grid.set_visible(matches!(*row, Row::Tiles { .. }));
+
+        match &*row {
+            Row::Header {
+                info,
+                count,
+                collapsed,
+                slow,
+                ..
+            } => {
+                let header = &widgets.header;
+                header.icon.set_icon_name(Some(&info.icon));
+                header.icon.set_visible(!self.icons_hidden.get());
+                header.name.set_label(&info.name);
+                header.count.set_label(&count.to_string());
+                header.count.set_visible(*collapsed);
+                header.slow.set_visible(slow.is_some());
+                header.slow.set_tooltip_text(
+                    slow.map(|ms| format!("Timed out after {:.1}s", ms as f64 / 1000.0))
+                        .as_deref(),
+                );
+            }
+            Row::Match { plugin_match, .. } => {
+                let list = &widgets.list;
+                let content = &plugin_match.content;
+                set_icon(&list.icon, &content.icon, self.icons_hidden.get());
+                list.title.set_use_markup(content.use_pango);
+                list.title.set_label(&content.title);
+                list.description.set_use_markup(content.use_pango);
+                match &content.description {
+                    ROption::RSome(desc) => {
+                        list.description.set_label(desc);
+                        list.description.set_visible(true);
+                    }
+                    ROption::RNone => list.description.set_visible(false),
+                }
+                list.plugin_name.set_label(&plugin_match.plugin_info.name);
+                list.plugin_name.set_visible(self.plugin_names.get());
+                list.actions.set_visible(!plugin_match.actions.is_empty());
+                self.sync_marked(&list.root, plugin_match);
+            }
+            Row::Tiles {
+                matches, columns, ..
+            } => {
+                widgets.ensure_tiles(self, item, *columns);
+                for (i, tile) in widgets.tiles.borrow().iter().enumerate() {
+                    // Unused tiles of the last row keep their place, so the columns line up
+                    let Some(plugin_match) = matches.get(i) else {
+                        tile.root.set_opacity(0.0);
+                        tile.root.set_can_target(false);
+                        continue;
+                    };
+                    let content = &plugin_match.content;
+                    tile.root.set_opacity(1.0);
+                    tile.root.set_can_target(true);
+                    tile.root.set_tooltip_text(
+                        content
+                            .description
+                            .as_ref()
+                            .map(RString::as_str)
+                            .into_option(),
+                    );
+                    set_icon(&tile.icon, &content.icon, self.icons_hidden.get());
+                    tile.title.set_use_markup(content.use_pango);
+                    tile.title.set_label(&content.title);
+                    self.sync_marked(&tile.root, plugin_match);
+                }
+            }
+        }
+        drop(row);
+
+        self.sync_item(item, widgets);
+    }
+
+    fn sync_marked(&self, widget: &gtk::Box, plugin_match: &PluginMatch) {
+        let marked = self.marked.borrow();
+        if marked.iter().any(|marked| marked.same(plugin_match)) {
+            widget.add_css_class("marked");
+        } else {
+            widget.remove_css_class("marked");
+        }
+    }
+
+    fn rebind_all(self: &Rc<Self>) {
+        let items = self.items.borrow().keys().cloned().collect::<Vec<_>>();
+        for item in items {
+            self.bind(&item);
+        }
+    }
+
+    fn sync_selection(&self) {
+        for (item, widgets) in self.items.borrow().iter() {
+            self.sync_item(item, widgets);
+        }
+    }
+
+    /// Updates which match of the item looks selected
+    fn sync_item(&self, item: &gtk::ListItem, widgets: &RowWidgets) {
+        let spans = self.spans.borrow();
+        let Some(span) = spans.get(item.position() as usize) else {
+            return;
+        };
+        let selected = self
+            .selected
+            .get()
+            .and_then(|selected| selected.checked_sub(span.start))
+            .filter(|offset| *offset < span.len);
+
+        set_selected(&widgets.list.root, !span.tiles && selected.is_some());
+        for (i, tile) in widgets.tiles.borrow().iter().enumerate() {
+            set_selected(&tile.root, span.tiles && selected == Some(i));
+        }
+    }
+
+    /// Calls `on_match` with the index of the match in the `slot` of the item
+    fn clicked(&self, item: &gtk::ListItem, slot: usize) {
+        let index = {
+            let spans = self.spans.borrow();
+            spans
+                .get(item.position() as usize)
+                .filter(|span| slot < span.len)
+                .map(|span| span.start + slot)
+        };
+        if let Some(index) = index {
+            (self.on_match)(index);
+        }
+    }
+}
+
+fn set_selected(widget: &impl IsA<gtk::Widget>, selected: bool) {
+    if selected {
+        widget.set_state_flags(gtk::StateFlags::SELECTED, false);
+    } else {
+        widget.unset_state_flags(gtk::StateFlags::SELECTED);
+    }
+}
+
+/// Loads the icon even when hidden, as icons can be toggled at runtime
+fn set_icon(image: &gtk::Image, icon: &ROption<RString>, hidden: bool) {
+    match icon {
+        ROption::RSome(icon) => {
+            let path = PathBuf::from(icon.to_string());
+            if path.is_absolute() {
+                image.set_from_file(Some(path));
+            } else {
+                image.set_icon_name(Some(icon));
+            }
+            image.set_visible(!hidden);
+        }
+        ROption::RNone => {
+            image.clear();
+            image.set_visible(false);
+        }
+    }
+}
+
+/// The widgets of a list item, which can show any kind of row
+struct RowWidgets {
+    root: gtk::Box,
+    header: HeaderWidgets,
+    list: MatchWidgets,
+    grid: gtk::Box,
+    tiles: RefCell<Vec<TileWidgets>>,
+}
+
+struct HeaderWidgets {
+    root: gtk::Box,
+    icon: gtk::Image,
+    name: gtk::Label,
+    count: gtk::Label,
+    /// Shown when the plugin didn't answer the query in time
+    slow: gtk::Label,
+}
+
+struct MatchWidgets {
+    root: gtk::Box,
+    icon: gtk::Image,
+    title: gtk::Label,
+    description: gtk::Label,
+    plugin_name: gtk::Label,
+    actions: gtk::Image,
+}
+
+struct TileWidgets {
+    root: gtk::Box,
+    icon: gtk::Image,
+    title: gtk::Label,
+}
+
+impl RowWidgets {
+    fn new(shared: &Rc<Shared>, item: &gtk::ListItem) -> Self {
+        let header = HeaderWidgets {
+            root: gtk::Box::builder()
+                .orientation(gtk::Orientation::Horizontal)
+                .css_classes(["plugin", "info"])
+                .build(),
+            icon: gtk::Image::builder()
+                .css_classes(["plugin", "info"])
+                .halign(gtk::Align::Start)
+                .valign(gtk::Align::Start)
+                .pixel_size(32)
+                .build(),
+            name: gtk::Label::builder()
+                .css_classes(["plugin", "info"])
+                .halign(gtk::Align::Start)
+                .valign(gtk::Align::Center)
+                .build(),
+            count: gtk::Label::builder()
+                .css_classes(["plugin", "info", "count"])
+                .halign(gtk::Align::Start)
+                .valign(gtk::Align::Center)
+                .build(),
+            slow: gtk::Label::builder()
+                .label("slow")
+                .css_classes(["plugin", "info", "slow"])
+                .halign(gtk::Align::Start)
+                .valign(gtk::Align::Center)
+                .build(),
+        };
+        header.root.append(&header.icon);
+        header.root.append(&header.name);
+        header.root.append(&header.count);
+        header.root.append(&header.slow);
+        let gesture = gtk::GestureClick::new();
+        let (weak_shared, weak_item) = (Rc::downgrade(shared), item.downgrade());
+        gesture.connect_released(move |_, _, _, _| {
+            let (Some(shared), Some(item)) = (weak_shared.upgrade(), weak_item.upgrade()) else {
+                return;
+            };
+            let plugin = shared
+                .spans
+                .borrow()
+                .get(item.position() as usize)
+                .map(|span| span.plugin);
+            if let Some(plugin) = plugin {
+                (shared.on_header)(plugin);
+            }
+        });
+        header.root.add_controller(gesture);
+
+        let list = MatchWidgets {
+            root: gtk::Box::builder()
+                .orientation(gtk::Orientation::Horizontal)
+                .spacing(10)
+                .css_classes(["match"])
+                .height_request(32)
+                .hexpand(true)
+                .build(),
+            icon: gtk::Image::builder()
+                .pixel_size(32)
+                .visible(false)
+                .css_classes(["match"])
+                .build(),
+            title: gtk::Label::builder()
+                .css_classes(["match", "title"])
+                .halign(gtk::Align::Start)
+                .valign(gtk::Align::Center)
+                .xalign(0.0)
+                .wrap(true)
+                .natural_wrap_mode(gtk::NaturalWrapMode::Word)
+                .wrap_mode(pango::WrapMode::WordChar)
+                .build(),
+            description: gtk::Label::builder()
+                .css_classes(["match", "description"])
+                .wrap(true)
+                .xalign(0.0)
+                .halign(gtk::Align::Start)
+                .valign(gtk::Align::Center)
+                .build(),
+            // Without the plugin headers of the grouped view, the plugin is shown per match
+            plugin_name: gtk::Label::builder()
+                .css_classes(["match", "plugin-name"])
+                .halign(gtk::Align::End)
+                .valign(gtk::Align::Center)
+                .build(),
+            actions: gtk::Image::builder()
+                .css_classes(["match", "actions"])
+                .icon_name("view-more-symbolic")
+                .halign(gtk::Align::End)
+                .valign(gtk::Align::Center)
+                .build(),
+        };
+        let text = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .css_classes(["match", "text-fields"])
+            .valign(gtk::Align::Center)
+            .hexpand(true)
+            .vexpand(true)
+            .build();
+        text.append(&list.title);
+        text.append(&list.description);
+        list.root.append(&list.icon);
+        list.root.append(&text);
+        list.root.append(&list.plugin_name);
+        list.root.append(&list.actions);
+        list.root.add_controller(click_gesture(shared, item, 0));
+
+        let grid = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .css_classes(["plugin", "grid"])
+            .homogeneous(true)
+            .hexpand(true)
+            .build();
+
+        let root = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .hexpand(true)
+            .build();
+        root.append(&header.root);
+        root.append(&list.root);
+        root.append(&grid);
+
+        Self {
+            root,
+            header,
+            list,
+            grid,
+            tiles: RefCell::default(),
+        }
+    }
+
+    /// Adds or removes tiles so there is one per column
+    fn ensure_tiles(&self, shared: &Rc<Shared>, item: &gtk::ListItem, columns: usize) {
+        let mut tiles = self.tiles.borrow_mut();
+        while tiles.len() > columns {
+            let tile = tiles.pop().unwrap();
+            self.grid.remove(&tile.root);
+        }
+        while tiles.len() < columns {
+            let tile = TileWidgets {
+                root: gtk::Box::builder()
+                    .orientation(gtk::Orientation::Vertical)
+                    .spacing(5)
+                    .css_classes(["match", "tile"])
+                    .build(),
+                icon: gtk::Image::builder()
+                    .pixel_size(48)
+                    .visible(false)
+                    .css_classes(["match", "tile"])
+                    .build(),
+                title: gtk::Label::builder()
+                    .css_classes(["match", "title"])
+                    .justify(gtk::Justification::Center)
+                    .wrap(true)
+                    .wrap_mode(pango::WrapMode::WordChar)
+                    .lines(2)
+                    .ellipsize(pango::EllipsizeMode::End)
+                    .max_width_chars(12)
+                    .build(),
+            };
+            tile.root.append(&tile.icon);
+            tile.root.append(&tile.title);
+            tile.root
+                .add_controller(click_gesture(shared, item, tiles.len()));
+            self.grid.append(&tile.root);
+            tiles.push(tile);
+        }
+    }
+}
+
+fn click_gesture(shared: &Rc<Shared>, item: &gtk::ListItem, slot: usize) -> gtk::GestureClick {
+    let gesture = gtk::GestureClick::new();
+    let (shared, item) = (Rc::downgrade(shared), item.downgrade());
+    gesture.connect_released(move |_, _, _, _| {
+        if let (Some(shared), Some(item)) = (shared.upgrade(), item.upgrade()) {
+            shared.clicked(&item, slot);
+        }
+    });
+    gesture
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(start: usize, plugin: usize) -> Span {
+        Span {
+            start,
+            len: 0,
+            plugin,
+            tiles: false,
+        }
+    }
+
+    fn tiles(start: usize, len: usize, plugin: usize) -> Span {
+        Span {
+            start,
+            len,
+            plugin,
+            tiles: true,
+        }
+    }
+
+    /// A grid of 5 matches in 2 columns, followed by a plugin with a single match in a list
+    fn spans() -> Vec<Span> {
+        vec![
+            header(0, 0),
+            tiles(0, 2, 0),
+            tiles(2, 2, 0),
+            tiles(4, 1, 0),
+            header(5, 1),
+            Span {
+                start: 5,
+                len: 1,
+                plugin: 1,
+                tiles: false,
+            },
+        ]
+    }
+
+    #[test]
+    fn steps_keep_the_column() {
+        let spans = spans();
+        assert_eq!(grid_step(&spans, 1, true), Some(3));
+        assert_eq!(grid_step(&spans, 3, false), Some(1));
+        assert_eq!(grid_step(&spans, 4, false), Some(2));
+    }
+
+    #[test]
+    fn shorter_last_row_takes_its_last_tile() {
+        let spans = spans();
+        assert_eq!(grid_step(&spans, 3, true), Some(4));
+        assert_eq!(grid_step(&spans, 2, true), Some(4));
+    }
+
+    #[test]
+    fn steps_past_the_grid_leave_it() {
+        let spans = spans();
+        assert_eq!(grid_step(&spans, 4, true), Some(5));
+        // Before the first match, wrapping around to the last one
+        assert_eq!(grid_step(&spans, 1, false), Some(5));
+    }
+
+    #[test]
+    fn steps_past_the_last_match_wrap_around() {
+        let spans = vec![tiles(0, 2, 0), tiles(2, 1, 0)];
+        assert_eq!(grid_step(&spans, 2, true), Some(0));
+        assert_eq!(grid_step(&spans, 0, false), Some(2));
+    }
+
+    #[test]
+    fn adjacent_grids_of_other_plugins_are_left() {
+        let spans = vec![tiles(0, 2, 0), tiles(2, 2, 1)];
+        assert_eq!(grid_step(&spans, 1, true), Some(2));
+        assert_eq!(grid_step(&spans, 3, false), Some(1));
+    }
+
+    #[test]
+    fn matches_outside_of_grids_are_not_stepped() {
+        let spans = spans();
+        assert_eq!(grid_step(&spans, 5, true), None);
+        assert_eq!(grid_step(&spans, 6, true), None);
+    }
+}
@@ -16,9 +16,10 @@ let
   inherit (lib.modules) mkIf mkMerge;
   inherit (lib.options) mkOption mkEnableOption literalExpression;
   inherit (lib.lists) optional;
-  inherit (lib.attrsets) mapAttrs' nameValuePair;
+  inherit (lib.attrsets) mapAttrs' mapAttrsToList nameValuePair;
   inherit (lib.strings)
     concatMapStringsSep
+    concatStringsSep
     toLower
     toUpper
     replaceStrings
@@ -32,6 +33,7 @@ let
     int
     float
     listOf
+    attrsOf
     either
     str
     enum
@@ -166,6 +168,38 @@ in
           '';
         };
 
+        position = mkOption {
+          type = nullOr (enum [
+            "top"
+            "center"
+            "bottom"
+            "topLeft"
+            "topRight"
+            "bottomLeft"
+            "bottomRight"
+          ]);
+          default = null;
+          description = "Anchor the runner to an edge, corner or the center of the screen instead of placing it with x and y";
+        };
+
+        offsetX = mkNumericOption {
+          default.absolute = 0;
+          description = ''
+            Distance from the anchored left or right edge when position is set.
+
+            ${numericInfo}
+          '';
+        };
+
+        offsetY = mkNumericOption {
+          default.absolute = 0;
+          description = ''
+            Distance from the anchored top or bottom edge when position is set.
+
+            ${numericInfo}
+          '';
+        };
+
         hideIcons = mkOption {
           type = bool;
           default = false;
@@ -222,6 +256,142 @@ in
           description = "Limit amount of entries shown in total";
         };
 
+        monitor = mkOption {
+          type = either (enum [
+            "focused"
+            "primary"
+          ]) (either int str);
+          default = "focused";
+          example = "DP-1";
+          description = "The monitor to show Anyrun on: the focused or primary one, an index into the list of monitors, or a connector name";
+        };
+
+        resultMode = mkOption {
+          type = enum [
+            "grouped"
+            "mixed"
+          ];
+          default = "grouped";
+          description = "Group matches by plugin, or interleave them in a single list by relevance";
+        };
+
+        pluginWeights = mkOption {
+          type = attrsOf float;
+          default = { };
+          example = literalExpression ''{ Applications = 1.2; }'';
+          description = "Relevance multipliers for the matches of each plugin in the mixed result mode, keyed by the plugin name";
+        };
+
+        prompt = mkOption {
+          type = nullOr str;
+          default = null;
+          description = "Placeholder text shown in the empty entry";
+        };
+
+        cssClass = mkOption {
+          type = nullOr str;
+          default = null;
+          description = "Extra style class added to the window";
+        };
+
+        profiles = mkOption {
+          type = attrsOf lines;
+          default = { };
+          example = literalExpression ''
+            {
+              clipboard = '''
+                Profile(
+                  plugins: ["libkde_clipboard.so"],
+                  prompt: Some("Clipboard"),
+                )
+              ''';
+            }
+          '';
+          description = "Named sets of overrides in RON, selected with `anyrun --profile <name>`";
+        };
+
+        pluginSettings = mkOption {
+          type = attrsOf (submodule {
+            options = {
+              maxEntries = mkOption {
+                type = nullOr int;
+                default = null;
+                description = "Maximum amount of matches shown from the plugin";
+              };
+              priority = mkOption {
+                type = int;
+                default = 0;
+                description = "Plugins with a higher priority are shown first";
+              };
+              collapsed = mkOption {
+                type = bool;
+                default = false;
+                description = "Only show the plugin header until expanded";
+              };
+              emptyQuery = mkOption {
+                type = bool;
+                default = true;
+                description = "Query the plugin when the entry is empty";
+              };
+              layout = mkOption {
+                type = enum [
+                  "list"
+                  "grid"
+                ];
+                default = "list";
+                description = "Show the matches as a list, or as a grid of tiles";
+              };
+              columns = mkOption {
+                type = int;
+                default = 6;
+                description = "Amount of tiles in each row of a grid";
+              };
+            };
+          });
+          default = { };
+          example = literalExpression ''{ Applications = { maxEntries = 5; priority = 1; }; }'';
+          description = "Result limits, ordering and visibility of each plugin, keyed by the plugin name";
+        };
+
+        frecency = mkOption {
+          type = bool;
+          default = true;
+          description = "Boost matches based on how often and how recently they have been selected";
+        };
+
+        frecencyExclude = mkOption {
+          type = listOf str;
+          default = [ ];
+          description = "Names of plugins whose matches are not affected by frecency";
+        };
+
+        historySize = mkOption {
+          type = int;
+          default = 100;
+          description = "Amount of past queries to remember, 0 disables the history";
+        };
+
+        restoreLastQuery = mkOption {
+          type = bool;
+          default = false;
+          description = "Fill the entry with the last query when shown";
+        };
+
+        showPreview = mkOption {
+          type = bool;
+          default = false;
+          description = "Show the preview pane for the selected match when opened";
+        };
+
+        previewPosition = mkOption {
+          type = enum [
+            "right"
+            "bottom"
+          ];
+          default = "right";
+          description = "Where the preview pane is placed relative to the matches";
+        };
+
         keybinds = mkOption {
           type = nullOr (
             listOf (submodule {
@@ -248,6 +418,26 @@ in
                     "select"
                     "up"
                     "down"
+                    "left"
+                    "right"
+                    "historyPrev"
+                    "historyNext"
+                    "historySearch"
+                    "actionMenu"
+                    "togglePreview"
+                    "pageUp"
+                    "pageDown"
+                    "first"
+                    "last"
+                    "nextPlugin"
+                    "prevPlugin"
+                    "clearEntry"
+                    "copySelectionTitle"
+                    "selectAndKeepOpen"
+                    "toggleIcons"
+                    "toggleCollapsed"
+                    "toggleMark"
+                    "acceptMarked"
                   ];
                 };
               };
@@ -309,6 +499,9 @@ in
       capitalize =
         string: toUpper (substring 0 1 string) + toLower (substring 1 ((stringLength string) - 1) string);
 
+      # Like `capitalize`, but keeps the case of the rest of the string for camelCase values
+      upperFirst = string: toUpper (substring 0 1 string) + substring 1 ((stringLength string) - 1) string;
+
       parsedPlugins =
         if cfg.config.plugins == null then
           [ ]
@@ -347,11 +540,25 @@ in
                     ${optionalString x.ctrl "ctrl: true,"}
                     ${optionalString x.alt "alt: true,"}
                     key: "${x.key}",
-                    action: ${capitalize x.action},
+                    action: ${upperFirst x.action},
                   ),
                 '') cfg.config.keybinds
               }],
           '';
+      pluginSettings = "{${
+        concatStringsSep ", " (
+          mapAttrsToList (name: x: ''
+            "${name}": PluginSettings(
+              max_entries: ${if x.maxEntries == null then "None" else "Some(${toString x.maxEntries})"},
+              priority: ${toString x.priority},
+              collapsed: ${boolToString x.collapsed},
+              empty_query: ${boolToString x.emptyQuery},
+              layout: ${capitalize x.layout},
+              columns: ${toString x.columns},
+            )'') cfg.config.pluginSettings
+        )
+      }}";
+      optionalRonString = value: if value == null then "None" else "Some(${toJSON value})";
       keyboardMode =
         {
           "exclusive" = "Exclusive";
@@ -365,6 +572,8 @@ in
         (assertNumeric cfg.config.height)
         (assertNumeric cfg.config.x)
         (assertNumeric cfg.config.y)
+        (assertNumeric cfg.config.offsetX)
+        (assertNumeric cfg.config.offsetY)
 
         {
           assertion = (builtins.hasAttr "anyrun-provider" cfg.package) || cfg.config.provider != null;
@@ -423,6 +632,11 @@ in
               y: ${stringifyNumeric cfg.config.y},
               width: ${stringifyNumeric cfg.config.width},
               height: ${stringifyNumeric cfg.config.height},
+              position: ${
+                if cfg.config.position == null then "None" else "Some(${upperFirst cfg.config.position})"
+              },
+              offset_x: ${stringifyNumeric cfg.config.offsetX},
+              offset_y: ${stringifyNumeric cfg.config.offsetY},
               hide_icons: ${boolToString cfg.config.hideIcons},
               ignore_exclusive_zones: ${boolToString cfg.config.ignoreExclusiveZones},
               layer: ${capitalize cfg.config.layer},
@@ -433,6 +647,30 @@ in
               max_entries: ${
                 if cfg.config.maxEntries == null then "None" else "Some(${toString cfg.config.maxEntries})"
               },
+              result_mode: ${capitalize cfg.config.resultMode},
+              monitor: ${
+                if cfg.config.monitor == "focused" || cfg.config.monitor == "primary" then
+                  capitalize cfg.config.monitor
+                else if builtins.isInt cfg.config.monitor then
+                  "ByIndex(${toString cfg.config.monitor})"
+                else
+                  "ByName(${toJSON cfg.config.monitor})"
+              },
+              plugin_weights: ${toJSON cfg.config.pluginWeights},
+              plugin_settings: ${pluginSettings},
+              prompt: ${optionalRonString cfg.config.prompt},
+              css_class: ${optionalRonString cfg.config.cssClass},
+              profiles: {${
+                concatStringsSep ", " (
+                  mapAttrsToList (name: profile: "${toJSON name}: ${profile}") cfg.config.profiles
+                )
+              }},
+              frecency: ${boolToString cfg.config.frecency},
+              frecency_exclude: ${toJSON cfg.config.frecencyExclude},
+              history_size: ${toString cfg.config.historySize},
+              restore_last_query: ${boolToString cfg.config.restoreLastQuery},
+              show_preview: ${boolToString cfg.config.showPreview},
+              preview_position: ${capitalize cfg.config.previewPosition},
               plugins: ${toJSON parsedPlugins},
               ${optionalString (cfg.config.provider != null) "provider: \"${lib.getExe cfg.config.provider}\","}
               ${optionalString (cfg.config.extraLines != null) cfg.config.extraLines}
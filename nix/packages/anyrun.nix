@@ -38,6 +38,7 @@ rustPlatform.buildRustPackage {
       (s + /anyrun)
       (s + /anyrun-macros)
       (s + /anyrun-plugin)
+      (s + /anyrun-provider)
       (s + /plugins)
       (s + /Cargo.toml)
       (s + /Cargo.lock)
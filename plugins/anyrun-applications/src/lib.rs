@@ -1,4 +1,5 @@
 use abi_stable::std_types::{ROption, RString, RVec};
+use anyrun_helper::actions::{handle_path_action, path_actions};
 use anyrun_plugin::{anyrun_interface::HandleResult, *};
 use fuzzy_matcher::FuzzyMatcher;
 use scrubber::DesktopEntry;
@@ -107,6 +108,31 @@ pub fn handler(selection: Match, state: &State) -> HandleResult {
     HandleResult::Close
 }
 
+#[actions]
+pub fn actions(selection: &Match, state: &State) -> RVec<MatchAction> {
+    match desktop_file(selection, state) {
+        Some(_) => path_actions(),
+        None => RVec::new(),
+    }
+}
+
+#[action_handler]
+pub fn action_handler(selection: Match, action: RString, state: &State) -> HandleResult {
+    match desktop_file(&selection, state) {
+        Some(desktop_file) => handle_path_action(&action, desktop_file),
+        None => HandleResult::Close,
+    }
+}
+
+/// The `.desktop` file of the selected entry, custom actions don't have one
+fn desktop_file<'a>(selection: &Match, state: &'a State) -> Option<&'a PathBuf> {
+    state
+        .entry_map
+        .get(selection.id.as_ref().into_option()?)?
+        .desktop_file
+        .as_ref()
+}
+
 #[init]
 pub fn init(config_dir: RString) -> State {
     let config: Config = fs::read_to_string(format!("{}/applications.ron", config_dir))
@@ -139,6 +165,7 @@ pub fn init(config_dir: RString) -> State {
             is_action: true,
             offset: 0,
             path: None,
+            desktop_file: None,
         };
         raw_entries.push((entry, next_id));
         next_id += 1;
@@ -220,54 +247,11 @@ pub fn get_matches(input: RString, state: &State) -> RVec<Match> {
             .collect();
     }
 
-    let matcher = fuzzy_matcher::skim::SkimMatcherV2::default();
-    let tokens: Vec<&str> = input_trimmed.split_whitespace().collect();
-    const ACTION_VERBS: &[&str] = &["quit", "close", "exit", "kill", "stop", "restart"];
-    let has_action_verb = tokens.iter().any(|t| ACTION_VERBS.contains(t));
-
+    let query = Query::new(input_trimmed);
     let mut scored_results: Vec<(u64, i64)> = state
         .search_entries
         .iter()
-        .filter_map(|se| {
-            let mut score = 0;
-            for token in &tokens {
-                let s_title = matcher.fuzzy_match(&se.title_lc, token).unwrap_or(0);
-                let s_name = matcher.fuzzy_match(&se.name_lc, token).unwrap_or(0);
-                let s_desc = se
-                    .desc_lc
-                    .as_ref()
-                    .and_then(|d| matcher.fuzzy_match(d, token))
-                    .unwrap_or(0);
-                let s_key = se
-                    .keywords_lc
-                    .iter()
-                    .filter_map(|k| matcher.fuzzy_match(k, token))
-                    .max()
-                    .unwrap_or(0);
-
-                let best = s_title.max(s_name).max(s_desc).max(s_key);
-                if best == 0 {
-                    return None;
-                }
-
-                score += s_title * 10 + s_name * 8 + s_desc * 5 + s_key * 3;
-            }
-
-            score -= se.offset;
-            if se.is_action {
-                score = if has_action_verb {
-                    score * 3
-                } else {
-                    score / 2
-                };
-            }
-
-            if score > 0 {
-                Some((se.id, score))
-            } else {
-                None
-            }
-        })
+        .filter_map(|se| Some((se.id, query.score(se)?)))
         .collect();
 
     // Sắp xếp theo score giảm dần
@@ -283,6 +267,91 @@ pub fn get_matches(input: RString, state: &State) -> RVec<Match> {
         .collect()
 }
 
+#[score]
+pub fn score(input: RString, selection: &Match, state: &State) -> ROption<f64> {
+    let input_lc = input.to_lowercase();
+    let query = Query::new(input_lc.trim());
+    let id = selection.id.as_ref().into_option();
+    let Some(se) = state.search_entries.iter().find(|se| Some(&se.id) == id) else {
+        return ROption::RNone;
+    };
+    match (query.score(se), query.perfect_score()) {
+        (Some(score), perfect) if perfect > 0 => {
+            ROption::RSome((score as f64 / perfect as f64).clamp(0.0, 1.0))
+        }
+        _ => ROption::RNone,
+    }
+}
+
+/// The lowercased query split into tokens, each of which has to match an entry
+struct Query<'a> {
+    matcher: fuzzy_matcher::skim::SkimMatcherV2,
+    tokens: Vec<&'a str>,
+    has_action_verb: bool,
+}
+
+impl<'a> Query<'a> {
+    const ACTION_VERBS: &'static [&'static str] =
+        &["quit", "close", "exit", "kill", "stop", "restart"];
+
+    fn new(input_lc: &'a str) -> Self {
+        let tokens: Vec<&str> = input_lc.split_whitespace().collect();
+        let has_action_verb = tokens.iter().any(|t| Self::ACTION_VERBS.contains(t));
+        Self {
+            matcher: fuzzy_matcher::skim::SkimMatcherV2::default(),
+            tokens,
+            has_action_verb,
+        }
+    }
+
+    /// Fuzzy score of the entry, `None` if a token doesn't match it
+    fn score(&self, se: &SearchableEntry) -> Option<i64> {
+        let mut score = 0;
+        for token in &self.tokens {
+            let s_title = self.matcher.fuzzy_match(&se.title_lc, token).unwrap_or(0);
+            let s_name = self.matcher.fuzzy_match(&se.name_lc, token).unwrap_or(0);
+            let s_desc = se
+                .desc_lc
+                .as_ref()
+                .and_then(|d| self.matcher.fuzzy_match(d, token))
+                .unwrap_or(0);
+            let s_key = se
+                .keywords_lc
+                .iter()
+                .filter_map(|k| self.matcher.fuzzy_match(k, token))
+                .max()
+                .unwrap_or(0);
+
+            let best = s_title.max(s_name).max(s_desc).max(s_key);
+            if best == 0 {
+                return None;
+            }
+
+            score += s_title * 10 + s_name * 8 + s_desc * 5 + s_key * 3;
+        }
+
+        score -= se.offset;
+        if se.is_action {
+            score = if self.has_action_verb {
+                score * 3
+            } else {
+                score / 2
+            };
+        }
+
+        (score > 0).then_some(score)
+    }
+
+    /// The score of an entry whose every field is exactly the query, to bring the scores
+    /// reported to the provider into `0.0..=1.0`
+    fn perfect_score(&self) -> i64 {
+        self.tokens
+            .iter()
+            .map(|token| self.matcher.fuzzy_match(token, token).unwrap_or(0) * 26)
+            .sum()
+    }
+}
+
 // Hàm helper để tránh lặp code và giảm clone
 fn make_match(entry: &DesktopEntry, id: u64, config: &Config) -> Match {
     Match {
@@ -6,6 +6,8 @@ use crate::Config;
 pub struct DesktopEntry {
     pub exec: String,
     pub path: Option<PathBuf>,
+    /// The `.desktop` file the entry was read from
+    pub desktop_file: Option<PathBuf>,
     pub name: String,
     pub localized_name: Option<String>,
     pub keywords: Vec<String>,
@@ -90,6 +92,7 @@ impl DesktopEntry {
                                 exec
                             },
                             path: map.get("Path").map(PathBuf::from),
+                            desktop_file: Some(entry.path()),
                             name: map.get("Name")?.to_string(),
                             localized_name: lang_choices
                                 .localized_keys("Name")
@@ -164,6 +167,7 @@ impl DesktopEntry {
                                 None => continue,
                             },
                             path: entry.path.clone(),
+                            desktop_file: entry.desktop_file.clone(),
                             name: match map.get("Name") {
                                 Some(name) => name.to_string(),
                                 None => continue,
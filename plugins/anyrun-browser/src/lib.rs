@@ -1,6 +1,6 @@
 use abi_stable::std_types::{ROption, RString, RVec};
-use anyrun_helper::focus_to_class;
 use anyrun_helper::icon::{SystemIcon, get_icon_path};
+use anyrun_helper::{escape_markup, focus_to_class};
 use anyrun_plugin::*;
 use fuzzy_matcher::FuzzyMatcher;
 use fuzzy_matcher::skim::SkimMatcherV2;
@@ -193,3 +193,28 @@ fn handler(selection: Match, state: &State) -> HandleResult {
     }
     HandleResult::Close
 }
+
+#[preview]
+fn preview(selection: &Match, state: &State) -> ROption<Preview> {
+    let ROption::RSome(tab_id) = &selection.description else {
+        return ROption::RNone;
+    };
+
+    let cache = state.cache.lock().unwrap();
+    let Some(browser) = cache
+        .as_ref()
+        .and_then(|(_, tabs)| tabs.iter().find(|browser| browser.id == tab_id.as_str()))
+    else {
+        return ROption::RNone;
+    };
+
+    ROption::RSome(Preview::Markup(
+        format!(
+            "<b>{}</b>\n\n{}\n\n<small>{}</small>",
+            escape_markup(&browser.title),
+            escape_markup(&browser.url),
+            escape_markup(&browser.id)
+        )
+        .into(),
+    ))
+}
@@ -1,10 +1,12 @@
 use abi_stable::std_types::{ROption, RString, RVec};
+use anyrun_helper::actions::{handle_path_action, path_actions};
 use anyrun_plugin::*;
 use serde::{Deserialize, Serialize};
 use std::env;
 use std::fs::{self};
+use std::io::{BufRead, BufReader};
 use std::path::Path;
-use std::process::Command;
+use std::process::{Command, Stdio};
 use std::sync::OnceLock;
 
 static CONFIG: OnceLock<Config> = OnceLock::new();
@@ -90,13 +92,7 @@ impl<'a> SearchEngine<'a> {
             .join(".*")
     }
 
-    fn execute_search(
-        &self,
-        query: &str,
-        path: &str,
-        excludes: &[String],
-        scope_idx: Option<usize>,
-    ) -> Vec<Match> {
+    fn command(&self, query: &str, path: &str, excludes: &[String]) -> Command {
         let regex = self.build_regex(query);
 
         let mut cmd = Command::new("fd");
@@ -119,39 +115,115 @@ impl<'a> SearchEngine<'a> {
             cmd.arg(&regex).arg(path);
         }
 
-        let match_id = scope_idx.map(|i| i as u64).unwrap_or(u64::MAX);
+        cmd
+    }
 
-        cmd.output()
-            .map(|out| self.to_matches(&String::from_utf8_lossy(&out.stdout), match_id))
+    fn execute_search(&self, search: &Search) -> Vec<Match> {
+        self.command(search.query, search.path, search.excludes)
+            .output()
+            .map(|out| {
+                String::from_utf8_lossy(&out.stdout)
+                    .lines()
+                    .filter_map(|line| to_match(line, search.match_id))
+                    .collect()
+            })
             .unwrap_or_default()
     }
 
-    fn to_matches(&self, stdout: &str, match_id: u64) -> Vec<Match> {
-        stdout
-            .lines()
-            .filter_map(|line| {
-                let path = Path::new(line);
-                let name = path.file_name()?.to_str()?;
-
-                Some(Match {
-                    title: name.into(),
-                    description: ROption::RSome(line.to_string().into()),
-                    icon: ROption::RSome(
-                        (if path.is_dir() {
-                            "folder"
-                        } else {
-                            "text-x-generic"
-                        })
-                        .into(),
-                    ),
-                    use_pango: false,
-                    id: ROption::RSome(match_id),
-                })
-            })
-            .collect()
+    /// Pushes the files to the sink as `fd` finds them, instead of waiting for it to finish
+    fn stream_search(&self, search: &Search, sink: &MatchSink) {
+        let Ok(mut child) = self
+            .command(search.query, search.path, search.excludes)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            return;
+        };
+        let Some(stdout) = child.stdout.take() else {
+            return;
+        };
+
+        let mut batch = Vec::new();
+        for line in BufReader::new(stdout).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            batch.extend(to_match(&line, search.match_id));
+            // Stops once the query is outdated, there is no need to wait for the rest
+            if batch.len() >= BATCH_SIZE && !sink.push(std::mem::take(&mut batch).into()) {
+                let _ = child.kill();
+                break;
+            }
+        }
+        if !batch.is_empty() {
+            sink.push(batch.into());
+        }
+        let _ = child.wait();
+    }
+}
+
+/// How many files are shown at once while streaming
+const BATCH_SIZE: usize = 5;
+
+/// What to search for, from the prefix the input starts with
+struct Search<'a> {
+    query: &'a str,
+    path: &'a str,
+    excludes: &'a [String],
+    match_id: u64,
+}
+
+impl<'a> Search<'a> {
+    fn parse(input: &'a str, config: &'a Config, home: &'a str) -> Option<Self> {
+        let input = input.trim();
+        if input.is_empty() {
+            return None;
+        }
+
+        for (idx, scope) in config.scopes.iter().enumerate() {
+            if input.starts_with(&scope.prefix) {
+                return Some(Self {
+                    query: input.trim_start_matches(&scope.prefix).trim(),
+                    path: &scope.path,
+                    excludes: &scope.excludes,
+                    match_id: idx as u64,
+                });
+            }
+        }
+        if !config.prefix.is_empty() && input.starts_with(&config.prefix) {
+            return Some(Self {
+                query: input.trim_start_matches(&config.prefix).trim(),
+                path: home,
+                excludes: &[],
+                match_id: u64::MAX,
+            });
+        }
+
+        None
     }
 }
 
+fn to_match(line: &str, match_id: u64) -> Option<Match> {
+    let path = Path::new(line);
+    let name = path.file_name()?.to_str()?;
+
+    Some(Match {
+        title: name.into(),
+        description: ROption::RSome(line.to_string().into()),
+        icon: ROption::RSome(
+            (if path.is_dir() {
+                "folder"
+            } else {
+                "text-x-generic"
+            })
+            .into(),
+        ),
+        use_pango: false,
+        id: ROption::RSome(match_id),
+    })
+}
+
 // --- PLUGIN HOOKS ---
 
 #[init]
@@ -178,27 +250,19 @@ fn info() -> PluginInfo {
 
 #[get_matches]
 fn get_matches(input: RString, config: &Config) -> RVec<Match> {
-    let input_str = input.trim();
-    if input_str.is_empty() {
-        return RVec::new();
+    let home = env::var("HOME").unwrap_or_else(|_| "/".into());
+    match Search::parse(&input, config, &home) {
+        Some(search) => SearchEngine::new(config).execute_search(&search).into(),
+        None => RVec::new(),
     }
+}
 
-    let engine = SearchEngine::new(config);
+#[stream_matches]
+fn stream_matches(input: RString, sink: &MatchSink, config: &Config) {
     let home = env::var("HOME").unwrap_or_else(|_| "/".into());
-    for (idx, scope) in config.scopes.iter().enumerate() {
-        if input_str.starts_with(&scope.prefix) {
-            let query = input_str.trim_start_matches(&scope.prefix).trim();
-            return engine
-                .execute_search(query, &scope.path, &scope.excludes, Some(idx))
-                .into();
-        }
-    }
-    if !config.prefix.is_empty() && input_str.starts_with(&config.prefix) {
-        let query = input_str.trim_start_matches(&config.prefix).trim();
-        return engine.execute_search(query, &home, &[], None).into();
+    if let Some(search) = Search::parse(&input, config, &home) {
+        SearchEngine::new(config).stream_search(&search, sink);
     }
-
-    RVec::new()
 }
 
 #[handler]
@@ -226,3 +290,21 @@ fn handler(selection: Match) -> HandleResult {
 
     HandleResult::Close
 }
+
+#[actions]
+fn actions(_selection: &Match) -> RVec<MatchAction> {
+    path_actions()
+}
+
+#[action_handler]
+fn action_handler(selection: Match, action: RString) -> HandleResult {
+    match selection.description {
+        ROption::RSome(path) => handle_path_action(&action, Path::new(path.as_str())),
+        ROption::RNone => HandleResult::Close,
+    }
+}
+
+#[preview]
+fn preview(selection: &Match) -> ROption<Preview> {
+    selection.description.clone().map(Preview::File)
+}
@@ -1,10 +1,13 @@
 use abi_stable::std_types::{ROption, RString, RVec};
-use anyrun_helper::icon::SystemIcon;
+use anyrun_helper::{
+    actions::{handle_path_action, path_actions},
+    icon::SystemIcon,
+};
 use anyrun_plugin::*;
 use fuzzy_matcher::FuzzyMatcher;
 use fuzzy_matcher::skim::SkimMatcherV2;
 use serde::Deserialize;
-use std::{fs, process::Command};
+use std::{fs, path::Path, process::Command};
 
 pub struct State {
     config: Config,
@@ -135,3 +138,13 @@ fn handler(selection: Match) -> HandleResult {
 
     HandleResult::Close
 }
+
+#[actions]
+fn actions(_selection: &Match) -> RVec<MatchAction> {
+    path_actions()
+}
+
+#[action_handler]
+fn action_handler(selection: Match, action: RString) -> HandleResult {
+    handle_path_action(&action, Path::new(selection.title.as_str()))
+}
@@ -1,7 +1,8 @@
 use abi_stable::std_types::{ROption, RString, RVec};
+use anyrun_helper::escape_markup;
 use anyrun_plugin::*;
 use serde::Deserialize;
-use std::fs;
+use std::{collections::HashMap, fs, sync::Mutex};
 
 #[derive(Deserialize)]
 pub struct Config {
@@ -18,8 +19,23 @@ impl Default for Config {
     }
 }
 
+pub struct State {
+    config: Config,
+    /// Details of the definitions of the last lookup, keyed by the definition
+    details: Mutex<HashMap<String, Details>>,
+}
+
+/// What is shown in the preview of a definition
+struct Details {
+    word: String,
+    part_of_speech: String,
+    example: Option<String>,
+    synonyms: Vec<String>,
+}
+
 #[derive(Deserialize)]
 struct ApiResponse {
+    word: String,
     meanings: Vec<Meaning>,
 }
 
@@ -33,13 +49,21 @@ struct Meaning {
 #[derive(Deserialize)]
 struct Definition {
     definition: String,
+    #[serde(default)]
+    example: Option<String>,
+    #[serde(default)]
+    synonyms: Vec<String>,
 }
 
 #[init]
-pub fn init(config_dir: RString) -> Config {
-    match fs::read_to_string(format!("{}/dictionary.ron", config_dir)) {
+pub fn init(config_dir: RString) -> State {
+    let config = match fs::read_to_string(format!("{}/dictionary.ron", config_dir)) {
         Ok(content) => ron::from_str(&content).unwrap_or_default(),
         Err(_) => Config::default(),
+    };
+    State {
+        config,
+        details: Mutex::new(HashMap::new()),
     }
 }
 
@@ -49,7 +73,8 @@ pub fn handler(_match: Match) -> HandleResult {
 }
 
 #[get_matches]
-pub fn get_matches(input: RString, config: &Config) -> RVec<Match> {
+pub fn get_matches(input: RString, state: &State) -> RVec<Match> {
+    let config = &state.config;
     let input = if let Some(input) = input.strip_prefix(&config.prefix) {
         input.trim()
     } else {
@@ -73,29 +98,63 @@ pub fn get_matches(input: RString, config: &Config) -> RVec<Match> {
         }
     };
 
-    responses
-        .into_iter()
-        .flat_map(|response| {
-            response
-                .meanings
-                .into_iter()
-                .flat_map(|meaning| {
-                    meaning
-                        .definitions
-                        .into_iter()
-                        .map(|definition| Match {
-                            title: definition.definition.into(),
-                            description: ROption::RSome(meaning.part_of_speech.clone().into()),
-                            use_pango: false,
-                            icon: ROption::RSome("accessories-dictionary".into()),
-                            id: ROption::RNone,
-                        })
-                        .collect::<RVec<_>>()
-                })
-                .collect::<RVec<_>>()
-        })
-        .take(config.max_entries)
-        .collect()
+    let mut details = state.details.lock().unwrap();
+    details.clear();
+
+    let mut matches = RVec::new();
+    for response in responses {
+        for meaning in response.meanings {
+            for definition in meaning.definitions {
+                if matches.len() >= config.max_entries {
+                    return matches;
+                }
+
+                details.insert(
+                    definition.definition.clone(),
+                    Details {
+                        word: response.word.clone(),
+                        part_of_speech: meaning.part_of_speech.clone(),
+                        example: definition.example,
+                        synonyms: definition.synonyms,
+                    },
+                );
+                matches.push(Match {
+                    title: definition.definition.into(),
+                    description: ROption::RSome(meaning.part_of_speech.clone().into()),
+                    use_pango: false,
+                    icon: ROption::RSome("accessories-dictionary".into()),
+                    id: ROption::RNone,
+                });
+            }
+        }
+    }
+    matches
+}
+
+#[preview]
+pub fn preview(selection: &Match, state: &State) -> ROption<Preview> {
+    let details = state.details.lock().unwrap();
+    let Some(details) = details.get(selection.title.as_str()) else {
+        return ROption::RNone;
+    };
+
+    let mut markup = format!(
+        "<b>{}</b> <i>{}</i>\n\n{}",
+        escape_markup(&details.word),
+        escape_markup(&details.part_of_speech),
+        escape_markup(&selection.title)
+    );
+    if let Some(example) = &details.example {
+        markup.push_str(&format!("\n\n<i>\"{}\"</i>", escape_markup(example)));
+    }
+    if !details.synonyms.is_empty() {
+        markup.push_str(&format!(
+            "\n\n<b>Synonyms:</b> {}",
+            escape_markup(&details.synonyms.join(", "))
+        ));
+    }
+
+    ROption::RSome(Preview::Markup(markup.into()))
 }
 
 #[info]
@@ -156,3 +156,8 @@ fn handler(selection: Match, _state: &State) -> HandleResult {
 
     HandleResult::Close
 }
+
+#[preview]
+fn preview(selection: &Match) -> ROption<Preview> {
+    ROption::RSome(Preview::Text(selection.title.clone()))
+}
@@ -1,9 +1,10 @@
 use abi_stable::std_types::{ROption, RString, RVec};
+use anyrun_helper::actions::{handle_path_action, path_actions};
 use anyrun_plugin::{anyrun_interface::HandleResult, *};
 use fuzzy_matcher::FuzzyMatcher;
 use kidex_common::IndexEntry;
 use serde::Deserialize;
-use std::{fs, os::unix::prelude::OsStrExt, process::Command};
+use std::{fs, process::Command};
 
 #[derive(Deserialize)]
 struct Config {
@@ -19,69 +20,71 @@ impl Default for Config {
 pub struct State {
     config: Config,
     index: Vec<(usize, IndexEntry)>,
-    selection: Option<IndexEntry>,
 }
 
-enum IndexAction {
-    Open,
-    Copy,
-    CopyPath,
-    Back,
+/// Kidex specific action, in addition to the shared path actions
+const COPY_FILE: &str = "copy-file";
+
+impl State {
+    fn entry(&self, selection: &Match) -> Option<&IndexEntry> {
+        self.index
+            .iter()
+            .find(|(id, _)| selection.id == ROption::RSome(*id as u64))
+            .map(|(_, index_entry)| index_entry)
+    }
 }
 
-impl From<u64> for IndexAction {
-    fn from(value: u64) -> Self {
-        match value {
-            0 => Self::Open,
-            1 => Self::Copy,
-            2 => Self::CopyPath,
-            3 => Self::Back,
-            _ => unreachable!(),
+#[handler]
+pub fn handler(selection: Match, state: &State) -> HandleResult {
+    if let Some(index_entry) = state.entry(&selection) {
+        if let Err(why) = Command::new("xdg-open").arg(&index_entry.path).spawn() {
+            eprintln!("[kidex] Error running xdg-open: {why}");
         }
     }
+    HandleResult::Close
 }
 
-#[handler]
-pub fn handler(selection: Match, state: &mut State) -> HandleResult {
-    match &state.selection {
-        Some(index_entry) => match selection.id.unwrap().into() {
-            IndexAction::Open => {
-                if let Err(why) = Command::new("xdg-open").arg(&index_entry.path).spawn() {
-                    eprintln!("[kidex] Error running xdg-open: {why}");
-                }
+#[actions]
+pub fn actions(_selection: &Match) -> RVec<MatchAction> {
+    let mut actions = vec![MatchAction {
+        id: COPY_FILE.into(),
+        title: "Copy file".into(),
+        icon: ROption::RSome("edit-copy".into()),
+        keybind: ROption::RNone,
+    }];
+    actions.extend(path_actions());
+    actions.into()
+}
+
+#[action_handler]
+pub fn action_handler(selection: Match, action: RString, state: &State) -> HandleResult {
+    let Some(index_entry) = state.entry(&selection) else {
+        return HandleResult::Close;
+    };
+
+    match action.as_str() {
+        COPY_FILE => match fs::read(&index_entry.path) {
+            Ok(bytes) => HandleResult::Copy(bytes.into()),
+            Err(why) => {
+                eprintln!(
+                    "[kidex] Error reading file {}: {why}, not copying",
+                    index_entry.path.display()
+                );
                 HandleResult::Close
             }
-            IndexAction::Copy => match fs::read(&index_entry.path) {
-                Ok(bytes) => HandleResult::Copy(bytes.into()),
-                Err(why) => {
-                    eprintln!(
-                        "[kidex] Error reading file {}: {why}, not copying",
-                        index_entry.path.display()
-                    );
-                    HandleResult::Close
-                }
-            },
-            IndexAction::CopyPath => {
-                HandleResult::Copy(index_entry.path.clone().into_os_string().as_bytes().into())
-            }
-            IndexAction::Back => {
-                state.selection = None;
-                HandleResult::Refresh(false)
-            }
         },
-        None => {
-            let (_, index_entry) = state
-                .index
-                .iter()
-                .find(|(id, _)| selection.id == ROption::RSome(*id as u64))
-                .unwrap();
-
-            state.selection = Some(index_entry.clone());
-            HandleResult::Refresh(true)
-        }
+        action => handle_path_action(action, &index_entry.path),
     }
 }
 
+#[preview]
+pub fn preview(selection: &Match, state: &State) -> ROption<Preview> {
+    state
+        .entry(selection)
+        .map(|index_entry| Preview::File(index_entry.path.display().to_string().into()))
+        .into()
+}
+
 #[init]
 pub fn init(config_dir: RString) -> State {
     let config = match fs::read_to_string(format!("{config_dir}/kidex.ron")) {
@@ -95,90 +98,48 @@ pub fn init(config_dir: RString) -> State {
             Vec::new()
         }
     };
-    State {
-        config,
-        index,
-        selection: None,
-    }
+    State { config, index }
 }
 
 #[get_matches]
 pub fn get_matches(input: RString, state: &State) -> RVec<Match> {
-    match &state.selection {
-        Some(index_entry) => {
-            let path = index_entry.path.to_string_lossy();
-            vec![
-                Match {
-                    title: "Open File".into(),
-                    description: ROption::RSome(path.clone().into()),
-                    use_pango: false,
-                    id: ROption::RSome(IndexAction::Open as u64),
-                    icon: ROption::RSome("document-open".into()),
-                },
-                Match {
-                    title: "Copy File".into(),
-                    description: ROption::RSome(path.clone().into()),
-                    use_pango: false,
-                    id: ROption::RSome(IndexAction::Copy as u64),
-                    icon: ROption::RSome("edit-copy".into()),
-                },
-                Match {
-                    title: "Copy Path".into(),
-                    description: ROption::RSome(path.into()),
-                    use_pango: false,
-                    id: ROption::RSome(IndexAction::CopyPath as u64),
-                    icon: ROption::RSome("edit-copy".into()),
-                },
-                Match {
-                    title: "Back".into(),
-                    description: ROption::RNone,
-                    use_pango: false,
-                    id: ROption::RSome(IndexAction::Back as u64),
-                    icon: ROption::RSome("edit-undo".into()),
-                },
-            ]
-            .into()
-        }
-        None => {
-            let matcher = fuzzy_matcher::skim::SkimMatcherV2::default().smart_case();
-            let mut index = state
-                .index
-                .clone()
-                .into_iter()
-                .filter_map(|(id, index_entry)| {
-                    matcher
-                        .fuzzy_match(&index_entry.path.as_os_str().to_string_lossy(), &input)
-                        .map(|val| (index_entry, id, val))
-                })
-                .collect::<Vec<_>>();
+    let matcher = fuzzy_matcher::skim::SkimMatcherV2::default().smart_case();
+    let mut index = state
+        .index
+        .clone()
+        .into_iter()
+        .filter_map(|(id, index_entry)| {
+            matcher
+                .fuzzy_match(&index_entry.path.as_os_str().to_string_lossy(), &input)
+                .map(|val| (index_entry, id, val))
+        })
+        .collect::<Vec<_>>();
 
-            index.sort_by(|a, b| b.2.cmp(&a.2));
+    index.sort_by(|a, b| b.2.cmp(&a.2));
 
-            index.truncate(state.config.max_entries);
-            index
-                .into_iter()
-                .map(|(entry_index, id, _)| Match {
-                    title: entry_index
-                        .path
-                        .file_name()
-                        .map(|name| name.to_string_lossy().into())
-                        .unwrap_or("N/A".into()),
-                    description: entry_index
-                        .path
-                        .parent()
-                        .map(|path| path.display().to_string().into())
-                        .into(),
-                    use_pango: false,
-                    icon: ROption::RSome(if entry_index.directory {
-                        "folder".into()
-                    } else {
-                        "text-x-generic".into()
-                    }),
-                    id: ROption::RSome(id as u64),
-                })
-                .collect()
-        }
-    }
+    index.truncate(state.config.max_entries);
+    index
+        .into_iter()
+        .map(|(entry_index, id, _)| Match {
+            title: entry_index
+                .path
+                .file_name()
+                .map(|name| name.to_string_lossy().into())
+                .unwrap_or("N/A".into()),
+            description: entry_index
+                .path
+                .parent()
+                .map(|path| path.display().to_string().into())
+                .into(),
+            use_pango: false,
+            icon: ROption::RSome(if entry_index.directory {
+                "folder".into()
+            } else {
+                "text-x-generic".into()
+            }),
+            id: ROption::RSome(id as u64),
+        })
+        .collect()
 }
 
 #[info]